@@ -0,0 +1,218 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::Asset;
+
+/// Name of the file in the originals directory the fetcher keeps its state in.
+/// It is hidden so neither the transformer nor the stale file cleanup pick it up.
+pub const STATE_FILE_NAME: &str = ".fetcher-state.json";
+
+/// State the fetcher keeps between fetch cycles and restarts
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FetcherState {
+    /// Assets that failed to download, keyed by asset ID
+    #[serde(default)]
+    pub failed: BTreeMap<String, FailedAsset>,
+    /// When each asset was last part of the selected working set, keyed by asset ID
+    #[serde(default)]
+    pub last_shown: BTreeMap<String, DateTime<Utc>>,
+    /// Fingerprint of the files of the working set when all of them were last in place.
+    /// While the working set keeps this fingerprint, the originals directory is not scanned.
+    #[serde(default)]
+    pub synced: Option<String>,
+    /// Manifest of the assets in the working set, keyed by asset ID
+    #[serde(default)]
+    pub assets: BTreeMap<String, ManifestEntry>,
+    /// Why files were removed from the originals directory, keyed by file name
+    #[serde(default)]
+    pub removed: BTreeMap<String, RemovedFile>,
+}
+
+/// What the fetcher knows about an asset of the working set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Name of the image in the originals directory
+    pub file_name: String,
+    /// Base64 encoded SHA-1 of the image, if the source knows it
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// Where the source found the asset, e.g. the album it is in
+    #[serde(default)]
+    pub origin: Option<String>,
+    /// When the image was downloaded, unknown for images downloaded before the manifest existed
+    #[serde(default)]
+    pub downloaded_at: Option<DateTime<Utc>>,
+    /// When the asset was last listed by the source
+    pub last_seen: DateTime<Utc>,
+    /// Whether the asset was listed as preferred, e.g. as a memory, which leaves the working
+    /// set by design when the source stops listing it
+    #[serde(default)]
+    pub preferred: bool,
+    /// IDs of the assets this one stands for, e.g. the video of a Live Photo or the other
+    /// photos of a stack, which are not downloaded themselves
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub linked_assets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedFile {
+    pub asset_id: String,
+    pub reason: String,
+    pub removed_at: DateTime<Utc>,
+}
+
+/// Where an asset of the working set stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetStatus {
+    /// The image is in the originals directory
+    Synced,
+    /// The image is still to be downloaded
+    Pending,
+    /// The image failed to download in the last attempt
+    Failed,
+}
+
+/// How long the reason for removing a file is kept
+const REMOVED_RETENTION: chrono::Duration = chrono::Duration::days(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedAsset {
+    /// Number of fetch cycles in a row the asset failed to download in
+    pub failures: u32,
+    pub last_error: String,
+    /// The asset is not attempted again before this time
+    pub retry_after: DateTime<Utc>,
+}
+
+impl FetcherState {
+    /// Loads the state from the originals directory, starting afresh if there is none
+    pub fn load(originals_dir: &str) -> anyhow::Result<Self> {
+        let path = Path::new(originals_dir).join(STATE_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read fetcher state: {:?}", path))?;
+        match serde_json::from_str(&content) {
+            Ok(state) => Ok(state),
+            Err(e) => {
+                eprintln!("Ignoring unreadable fetcher state {:?}: {}", path, e);
+                Ok(Self::default())
+            }
+        }
+    }
+
+    /// Saves the state to the originals directory, replacing the previous state atomically
+    pub fn save(&self, originals_dir: &str) -> anyhow::Result<()> {
+        let path = Path::new(originals_dir).join(STATE_FILE_NAME);
+        let temp_path = Path::new(originals_dir).join(format!("{}.part", STATE_FILE_NAME));
+
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write fetcher state: {:?}", temp_path))?;
+        fs::rename(&temp_path, &path)
+            .with_context(|| format!("Failed to write fetcher state: {:?}", path))?;
+        Ok(())
+    }
+
+    /// Returns the failure record if the asset is still cooling down after failing
+    pub fn cooling_down(&self, asset_id: &str, now: DateTime<Utc>) -> Option<&FailedAsset> {
+        self.failed
+            .get(asset_id)
+            .filter(|failed| failed.retry_after > now)
+    }
+
+    pub fn record_failure(&mut self, asset_id: &str, error: &str, cooldown: Duration, now: DateTime<Utc>) {
+        let cooldown = chrono::Duration::from_std(cooldown).unwrap_or(chrono::Duration::MAX);
+        let retry_after = now.checked_add_signed(cooldown).unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let failures = self.failed.get(asset_id).map_or(0, |f| f.failures) + 1;
+
+        self.failed.insert(asset_id.to_string(), FailedAsset {
+            failures,
+            last_error: error.to_string(),
+            retry_after,
+        });
+    }
+
+    pub fn record_success(&mut self, asset_id: &str) {
+        self.failed.remove(asset_id);
+    }
+
+    /// Forgets about failed assets that are no longer wanted
+    pub fn retain_failed(&mut self, asset_ids: &HashSet<String>) {
+        self.failed.retain(|asset_id, _| asset_ids.contains(asset_id));
+    }
+
+    /// Records that the assets are part of the working set at `now`
+    pub fn mark_shown<'a>(&mut self, asset_ids: impl IntoIterator<Item = &'a String>, now: DateTime<Utc>) {
+        for asset_id in asset_ids {
+            self.last_shown.insert(asset_id.clone(), now);
+        }
+    }
+
+    /// Forgets when assets were shown once they are gone from the albums
+    pub fn retain_shown(&mut self, asset_ids: &HashSet<String>) {
+        self.last_shown.retain(|asset_id, _| asset_ids.contains(asset_id));
+    }
+
+    /// Records the asset as part of the working set, listed by the source at `now`.
+    /// Returns the previous entry of the asset, if any.
+    pub fn record_listed(&mut self, asset: &Asset, file_name: &str, checksum: Option<&str>, preferred: bool, now: DateTime<Utc>) -> Option<ManifestEntry> {
+        let previous = self.assets.get(&asset.id).cloned();
+        // The download time only carries over while the file stays the same
+        let downloaded_at = previous
+            .as_ref()
+            .filter(|entry| entry.file_name == file_name)
+            .and_then(|entry| entry.downloaded_at);
+
+        self.assets.insert(asset.id.clone(), ManifestEntry {
+            file_name: file_name.to_string(),
+            checksum: checksum.map(str::to_string),
+            origin: asset.origin.clone(),
+            downloaded_at,
+            last_seen: now,
+            preferred,
+            linked_assets: asset.linked_ids.clone(),
+        });
+        previous
+    }
+
+    /// Records that the image of the asset is in place since `at`
+    pub fn record_downloaded(&mut self, asset_id: &str, at: DateTime<Utc>) {
+        if let Some(entry) = self.assets.get_mut(asset_id) {
+            entry.downloaded_at = Some(at);
+            self.removed.remove(&entry.file_name);
+        }
+    }
+
+    /// Forgets about assets that left the working set
+    pub fn retain_assets(&mut self, asset_ids: &HashSet<String>) {
+        self.assets.retain(|asset_id, _| asset_ids.contains(asset_id));
+    }
+
+    /// Records why a file was removed, forgetting about files removed long ago
+    pub fn record_removed(&mut self, file_name: &str, asset_id: &str, reason: &str, now: DateTime<Utc>) {
+        self.removed.retain(|_, removed| now - removed.removed_at < REMOVED_RETENTION);
+        self.removed.insert(file_name.to_string(), RemovedFile {
+            asset_id: asset_id.to_string(),
+            reason: reason.to_string(),
+            removed_at: now,
+        });
+    }
+
+    /// Tells where an asset of the manifest stands, judging by the files in `originals_dir`
+    pub fn asset_status(&self, asset_id: &str, entry: &ManifestEntry, originals_dir: &str) -> AssetStatus {
+        if self.failed.contains_key(asset_id) {
+            AssetStatus::Failed
+        } else if Path::new(originals_dir).join(&entry.file_name).is_file() {
+            AssetStatus::Synced
+        } else {
+            AssetStatus::Pending
+        }
+    }
+}
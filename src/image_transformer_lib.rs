@@ -1,11 +1,12 @@
 use anyhow::Context;
-use notify::{Event, EventKind, event::RemoveKind, Config, RecommendedWatcher, Watcher, RecursiveMode};
+use notify::{Event, EventKind, event::{ModifyKind, RemoveKind}, Config, RecommendedWatcher, Watcher, RecursiveMode};
 use std::cmp::min;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::mpsc::Receiver;
 use std::time::Duration;
+use crate::is_hidden;
 
 pub trait TransformerConfig {
     fn originals_dir(&self) -> &str;
@@ -15,14 +16,14 @@ pub trait TransformerConfig {
 
 pub fn process_existing_files<T: TransformerConfig>(args: &T) -> anyhow::Result<()> {
     // Get list of files to process
-    let entries = fs::read_dir(&args.originals_dir())
+    let entries = fs::read_dir(args.originals_dir())
         .context("Failed to read originals directory")?;
 
     let files = entries
         .filter_map(|entry| {
             let entry = entry.ok()?;
             let path = entry.path();
-            if path.is_file() {
+            if path.is_file() && !is_hidden(&path) {
                 Some(path)
             } else {
                 None
@@ -70,18 +71,26 @@ fn handle_file_system_events<T: TransformerConfig>(
                     // Handle file creation or modification events
                     EventKind::Create(_) | EventKind::Modify(_) => {
                         for path in event.paths {
-                            if path.is_file() {
+                            // Hidden files are in-progress downloads and other fetcher bookkeeping
+                            if path.is_file() && !is_hidden(&path) {
                                 println!("New file detected: {:?}", path);
                                 match process_file(&path, args) {
                                     Ok(_) => println!("Successfully processed new file"),
                                     Err(e) => eprintln!("Error processing file: {}", e),
                                 }
+                            } else if matches!(event.kind, EventKind::Modify(ModifyKind::Name(_))) && !path.exists() && !is_hidden(&path) {
+                                // A file moved away, e.g. into the fetcher's trash, is gone like a removed one
+                                println!("File moved away: {:?}", path);
+                                match handle_removed_file(&path, args) {
+                                    Ok(_) => println!("Successfully handled removed file"),
+                                    Err(e) => eprintln!("Error handling removed file: {}", e),
+                                }
                             }
                         }
                     },
                     // Handle file removal events
                     EventKind::Remove(RemoveKind::File) => {
-                        for path in event.paths {
+                        for path in event.paths.into_iter().filter(|p| !is_hidden(p)) {
                             println!("File removed: {:?}", path);
                             match handle_removed_file(&path, args) {
                                 Ok(_) => println!("Successfully handled removed file"),
@@ -106,12 +115,22 @@ fn handle_file_system_events<T: TransformerConfig>(
     Ok(())
 }
 
+/// Returns true for the JSON metadata sidecars the fetcher writes next to the images
+fn is_sidecar(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
 /// Get the output path for a given input file path
 fn get_output_path(file_path: &Path, output_dir: &str) -> anyhow::Result<String> {
     let file_name = file_path.file_name()
         .context("Invalid file path")?
         .to_string_lossy();
 
+    // Sidecars keep their name, which shares the stem with the converted image
+    if is_sidecar(file_path) {
+        return Ok(format!("{}/{}", output_dir, file_name));
+    }
+
     // Generate output filename with same name but PNG extension
     let file_stem = Path::new(&*file_name).file_stem()
         .context("Failed to get file stem")?
@@ -122,7 +141,15 @@ fn get_output_path(file_path: &Path, output_dir: &str) -> anyhow::Result<String>
 }
 
 fn process_file<T: TransformerConfig>(file_path: &Path, args: &T) -> anyhow::Result<()> {
-    let output_path = get_output_path(file_path, &args.transformed_dir())?;
+    let output_path = get_output_path(file_path, args.transformed_dir())?;
+
+    // Sidecars are carried over as they are, replacing an outdated copy
+    if is_sidecar(file_path) {
+        fs::copy(file_path, &output_path)
+            .with_context(|| format!("Failed to copy sidecar to {}", output_path))?;
+        println!("Copied sidecar: {}", output_path);
+        return Ok(());
+    }
 
     // Check if output file already exists
     if Path::new(&output_path).exists() {
@@ -132,7 +159,7 @@ fn process_file<T: TransformerConfig>(file_path: &Path, args: &T) -> anyhow::Res
 
     let input_path = file_path.to_string_lossy();
     let status = Command::new("bash")
-        .arg(&args.conversion_script())
+        .arg(args.conversion_script())
         .arg(&*input_path)
         .arg(&output_path)
         .status()
@@ -150,7 +177,7 @@ fn process_file<T: TransformerConfig>(file_path: &Path, args: &T) -> anyhow::Res
 
 /// Handle a file that has been removed from the originals directory
 fn handle_removed_file<T: TransformerConfig>(file_path: &Path, args: &T) -> anyhow::Result<()> {
-    let output_path = get_output_path(file_path, &args.transformed_dir())?;
+    let output_path = get_output_path(file_path, args.transformed_dir())?;
 
     // Check if the output file exists
     if Path::new(&output_path).exists() {
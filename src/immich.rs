@@ -0,0 +1,611 @@
+use reqwest::{header, Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::metadata::{AssetInfo, Sidecar};
+use crate::rate_limit::{self, RateLimiter};
+use crate::search::{self, SearchFilter};
+use crate::{save_download, Asset, AssetList, HttpStatusError, PhotoSource};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AlbumResponse {
+    /// Empty when the album is fetched without its assets
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+    #[serde(rename = "updatedAt", default)]
+    pub updated_at: Option<String>,
+    #[serde(rename = "assetCount", default)]
+    pub asset_count: Option<usize>,
+    /// When one of the assets was last modified, e.g. archived, favourited or stacked
+    #[serde(rename = "lastModifiedAssetTimestamp", default)]
+    pub last_modified_asset_timestamp: Option<String>,
+}
+
+impl AlbumResponse {
+    fn version(&self) -> Option<AlbumVersion> {
+        Some(AlbumVersion {
+            updated_at: self.updated_at.clone()?,
+            asset_count: self.asset_count?,
+            last_modified_asset: self.last_modified_asset_timestamp.clone(),
+        })
+    }
+}
+
+/// Changes whenever assets are added to or removed from an album, or one of its assets is edited
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AlbumVersion {
+    updated_at: String,
+    asset_count: usize,
+    last_modified_asset: Option<String>,
+}
+
+/// The assets of an album as of the last full listing
+struct CachedAlbum {
+    version: AlbumVersion,
+    assets: Vec<Asset>,
+    listed_at: Instant,
+}
+
+/// How long the assets of an unchanged album are reused. Servers that do not report when
+/// the assets were last modified still catch up with archived or favourited assets then.
+const ALBUM_CACHE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Cookie through which Immich accepts the token of a password protected shared link
+const SHARED_LINK_TOKEN_COOKIE: &str = "immich_shared_link_token";
+
+/// Response of `/api/shared-links/me`: either an album or individually shared assets
+#[derive(Debug, Deserialize)]
+struct SharedLinkResponse {
+    #[serde(default)]
+    album: Option<SharedAlbum>,
+    #[serde(default)]
+    assets: Vec<Asset>,
+    /// Access token, only issued for password protected links
+    #[serde(default)]
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SharedAlbum {
+    id: String,
+}
+
+// Trait to abstract the API configuration
+pub trait ImmichConfig {
+    fn immich_url(&self) -> &str;
+    fn api_key(&self) -> &str;
+    fn album_ids(&self) -> &[String];
+
+    /// Key of a public shared link to take the images from instead of the albums, the
+    /// search and the memories, which need an API key
+    fn shared_link_key(&self) -> Option<&str> {
+        None
+    }
+
+    fn shared_link_password(&self) -> Option<&str> {
+        None
+    }
+
+    /// Assets matching this search are shown in addition to the albums
+    fn search_filter(&self) -> Option<&SearchFilter> {
+        None
+    }
+
+    /// What to do with video assets
+    fn video_mode(&self) -> VideoMode {
+        VideoMode::Skip
+    }
+
+    /// Which size of the images to download
+    fn asset_size(&self) -> AssetSize {
+        AssetSize::Original
+    }
+
+    /// Show archived assets too. Trashed, hidden and locked assets are never shown.
+    fn include_archived(&self) -> bool {
+        false
+    }
+
+    /// Show photos taken on today's date in previous years, falling back to the albums
+    fn memories(&self) -> Option<MemoriesConfig> {
+        None
+    }
+
+    /// The date memories are shown for, today in the local time zone
+    fn today(&self) -> chrono::NaiveDate {
+        chrono::Local::now().date_naive()
+    }
+
+    /// Limits the requests and downloads, shared by all sources of the fetcher
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        None
+    }
+}
+
+/// Settings of the "on this day" memories source
+#[derive(Debug, Clone, Copy)]
+pub struct MemoriesConfig {
+    /// How many years back to look for photos taken on today's date
+    pub years: u32,
+    /// With fewer memories than this, the albums and the search fill up the working set
+    pub min_assets: usize,
+}
+
+/// Which size of an image is downloaded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum AssetSize {
+    /// The original file as uploaded
+    #[default]
+    Original,
+    /// The preview Immich generates for its web interface, large enough for most frames
+    Preview,
+    /// A full resolution JPEG Immich generates for formats browsers cannot show, e.g. HEIC or RAW
+    Fullsize,
+}
+
+/// What to do with video assets, which the conversion script cannot handle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum VideoMode {
+    /// Leave videos out of the slideshow
+    #[default]
+    Skip,
+    /// Download the still preview Immich generated for the video
+    Preview,
+}
+
+/// The variant of an asset that is downloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rendition {
+    /// The original file as uploaded, verified against the Immich checksum
+    Original,
+    /// An image Immich generated for the asset, in the given thumbnail size
+    Thumbnail(&'static str),
+}
+
+/// Photos from an Immich server: the configured albums, search and memories
+pub struct ImmichSource<'a, T: ImmichConfig> {
+    client: &'a Client,
+    config: &'a T,
+    /// Token issued for a password protected shared link by the last listing
+    shared_link_token: Mutex<Option<String>>,
+    /// Albums as of their last full listing, keyed by album ID
+    albums: Mutex<HashMap<String, CachedAlbum>>,
+    /// The memories found for the day they were searched for
+    memories: Mutex<Option<(chrono::NaiveDate, Vec<Asset>)>>,
+}
+
+impl<'a, T: ImmichConfig> ImmichSource<'a, T> {
+    pub fn new(client: &'a Client, config: &'a T) -> Self {
+        ImmichSource {
+            client,
+            config,
+            shared_link_token: Mutex::new(None),
+            albums: Mutex::new(HashMap::new()),
+            memories: Mutex::new(None),
+        }
+    }
+
+    /// Authenticates a request with the shared link if one is configured, otherwise with the API key
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        let Some(key) = self.config.shared_link_key() else {
+            return request.header("x-api-key", self.config.api_key());
+        };
+
+        let request = request.query(&[("key", key)]);
+        match self.shared_link_token.lock().unwrap().as_deref() {
+            Some(token) => request.header(header::COOKIE, format!("{}={}", SHARED_LINK_TOKEN_COOKIE, token)),
+            None => request,
+        }
+    }
+
+    /// Tells whether an asset may be shown: not trashed, hidden (e.g. the video of a Live Photo)
+    /// or in the locked folder, and archived only if asked for
+    fn is_visible_asset(&self, asset: &Asset) -> bool {
+        let is_archived = asset.is_archived || asset.visibility.as_deref() == Some("archive");
+        !asset.is_trashed
+            && !matches!(asset.visibility.as_deref(), Some("hidden" | "locked"))
+            && (!is_archived || self.config.include_archived())
+    }
+
+    /// Tells whether an asset can be shown at all: images always, videos only as their preview
+    fn is_supported_asset(&self, asset: &Asset) -> bool {
+        if !self.is_visible_asset(asset) {
+            return false;
+        }
+
+        match asset.asset_type.as_str() {
+            "IMAGE" => true,
+            "VIDEO" => self.config.video_mode() == VideoMode::Preview,
+            _ => false,
+        }
+    }
+
+    /// Chooses which variant of the asset to download
+    fn rendition(&self, asset: &Asset) -> Rendition {
+        if asset.asset_type == "VIDEO" {
+            return Rendition::Thumbnail("preview");
+        }
+
+        match self.config.asset_size() {
+            AssetSize::Original => Rendition::Original,
+            AssetSize::Preview => Rendition::Thumbnail("preview"),
+            AssetSize::Fullsize => Rendition::Thumbnail("fullsize"),
+        }
+    }
+
+    async fn fetch_album(&self, album_id: &str, with_assets: bool) -> anyhow::Result<AlbumResponse> {
+        let url = format!("{}/api/albums/{}?withoutAssets={}",
+                          self.config.immich_url(), album_id, !with_assets);
+
+        let request = self.authorize(self.client.get(url))
+            .header(header::ACCEPT, "application/json");
+        let response = rate_limit::send(self.config.rate_limiter(), request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await?;
+            anyhow::bail!("Failed to fetch assets of album {}: HTTP {}: {}", album_id, status, text);
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Fetches the assets of an album. Once an album was listed, only its version is fetched,
+    /// and the assets are listed again when it changed or the listing is too old.
+    async fn fetch_album_asset_list(&self, album_id: &str) -> anyhow::Result<Vec<Asset>> {
+        let cached_version = self.albums
+            .lock()
+            .unwrap()
+            .get(album_id)
+            .filter(|album| album.listed_at.elapsed() < ALBUM_CACHE_MAX_AGE)
+            .map(|album| album.version.clone());
+        if let Some(cached_version) = cached_version {
+            let album = self.fetch_album(album_id, false).await?;
+            if album.version().as_ref() == Some(&cached_version) {
+                if let Some(cached) = self.albums.lock().unwrap().get(album_id) {
+                    println!("Album {} is unchanged since the last listing", album_id);
+                    return Ok(cached.assets.clone());
+                }
+            }
+        }
+
+        let album = self.fetch_album(album_id, true).await?;
+        let mut albums = self.albums.lock().unwrap();
+        match album.version() {
+            Some(version) => {
+                albums.insert(album_id.to_string(), CachedAlbum { version, assets: album.assets.clone(), listed_at: Instant::now() });
+            }
+            None => {
+                albums.remove(album_id);
+            }
+        }
+        Ok(album.assets)
+    }
+
+    /// Fetches the assets of the shared link: the album it shares or the individually shared assets
+    async fn fetch_shared_link_asset_list(&self, key: &str) -> anyhow::Result<Vec<Asset>> {
+        let url = format!("{}/api/shared-links/me", self.config.immich_url());
+
+        let mut query = vec![("key", key)];
+        if let Some(password) = self.config.shared_link_password() {
+            query.push(("password", password));
+        }
+        let request = self.client.get(url)
+            .header(header::ACCEPT, "application/json")
+            .query(&query);
+        let response = rate_limit::send(self.config.rate_limiter(), request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(anyhow::Error::new(HttpStatusError { status, body })
+                .context("Failed to open shared link"));
+        }
+
+        let shared_link: SharedLinkResponse = response.json().await?;
+        *self.shared_link_token.lock().unwrap() = shared_link.token;
+
+        match shared_link.album {
+            Some(album) => {
+                let mut assets = self.fetch_album_asset_list(&album.id).await?;
+                println!("Found {} assets in shared album {}", assets.len(), album.id);
+                for asset in &mut assets {
+                    asset.origin = Some(format!("shared album {}", album.id));
+                }
+                Ok(assets)
+            }
+            None => {
+                println!("Found {} shared assets", shared_link.assets.len());
+                let mut assets = shared_link.assets;
+                for asset in &mut assets {
+                    asset.origin = Some("shared link".to_string());
+                }
+                Ok(assets)
+            }
+        }
+    }
+
+    /// Fetches the assets of all configured albums and the search, keeping the first occurrence of each asset
+    async fn fetch_all_asset_list(&self) -> anyhow::Result<Vec<Asset>> {
+        let search_filter = self.config.search_filter().filter(|filter| filter.is_configured());
+        // An empty asset list would remove every original
+        if self.config.album_ids().is_empty() && search_filter.is_none() {
+            anyhow::bail!("Neither albums nor a search are configured");
+        }
+
+        let mut seen_ids = std::collections::HashSet::new();
+        let mut assets = Vec::new();
+
+        for album_id in self.config.album_ids() {
+            let album_assets = self.fetch_album_asset_list(album_id).await?;
+            println!("Found {} assets in album {}", album_assets.len(), album_id);
+
+            for mut asset in album_assets {
+                if seen_ids.insert(asset.id.clone()) {
+                    asset.origin = Some(format!("album {}", album_id));
+                    assets.push(asset);
+                }
+            }
+        }
+
+        if let Some(filter) = search_filter {
+            let search_assets = search::search_assets(self.client, self.config, filter).await?;
+            println!("Found {} assets matching the search", search_assets.len());
+
+            for mut asset in search_assets {
+                if seen_ids.insert(asset.id.clone()) {
+                    asset.origin = Some("search".to_string());
+                    assets.push(asset);
+                }
+            }
+        }
+
+        Ok(assets)
+    }
+
+    /// Tells whether albums or a search are configured besides the memories
+    fn has_regular_sources(&self) -> bool {
+        !self.config.album_ids().is_empty()
+            || self.config.search_filter().is_some_and(|filter| filter.is_configured())
+    }
+
+    /// Fetches the assets taken on today's date in each of the previous `years` years.
+    /// They are only searched for once a day.
+    async fn fetch_memories_asset_list(&self, memories: &MemoriesConfig) -> anyhow::Result<Vec<Asset>> {
+        use chrono::Datelike;
+        let today = self.config.today();
+        if let Some((date, assets)) = self.memories.lock().unwrap().as_ref() {
+            if *date == today {
+                return Ok(assets.clone());
+            }
+        }
+
+        let mut assets = Vec::new();
+
+        for years_ago in 1..=memories.years {
+            // February 29th only has memories in leap years
+            let Some(date) = today.year().checked_sub(years_ago as i32).and_then(|year| today.with_year(year)) else {
+                continue;
+            };
+            let (Some(after), Some(before)) = (local_start_of_day(date), date.succ_opt().and_then(local_start_of_day)) else {
+                continue;
+            };
+            assets.extend(search::search_taken_between(self.client, self.config, after, before, search::DEFAULT_SEARCH_LIMIT).await?);
+        }
+
+        for asset in &mut assets {
+            asset.origin = Some("memories".to_string());
+        }
+        *self.memories.lock().unwrap() = Some((today, assets.clone()));
+        Ok(assets)
+    }
+
+    /// Fetches the assets of the albums and the search that can be shown, leaving out the
+    /// ones already listed as `memories`
+    async fn fetch_regular_asset_list(&self, memories: &[Asset]) -> anyhow::Result<Vec<Asset>> {
+        let assets = self.fetch_all_asset_list().await?;
+        println!("Found {} unique assets", assets.len());
+
+        // Show each Live Photo and each stack once
+        let asset_count = assets.len();
+        let mut assets = collapse_linked_assets(assets);
+        if assets.len() < asset_count {
+            println!("Skipping {} Live Photo videos and photos stacked behind another one", asset_count - assets.len());
+        }
+
+        let asset_count = assets.len();
+        assets.retain(|asset| self.is_visible_asset(asset));
+        if assets.len() < asset_count {
+            println!("Skipping {} trashed, archived or hidden assets", asset_count - assets.len());
+        }
+
+        // Leave out videos and other assets that cannot be shown on a frame
+        let asset_count = assets.len();
+        assets.retain(|asset| self.is_supported_asset(asset));
+        if assets.len() < asset_count {
+            println!("Skipping {} videos and other non-image assets", asset_count - assets.len());
+        }
+
+        let memory_ids: std::collections::HashSet<&str> = memories.iter().map(|asset| asset.id.as_str()).collect();
+        assets.retain(|asset| !memory_ids.contains(asset.id.as_str()));
+        Ok(assets)
+    }
+
+    /// Fetches the full details of an asset: EXIF data, recognized people and tags
+    async fn fetch_asset_info(&self, asset_id: &str) -> anyhow::Result<AssetInfo> {
+        let url = format!("{}/api/assets/{}", self.config.immich_url(), asset_id);
+
+        let request = self.authorize(self.client.get(url))
+            .header(header::ACCEPT, "application/json");
+        let response = rate_limit::send(self.config.rate_limiter(), request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(anyhow::Error::new(HttpStatusError { status, body })
+                .context("Failed to fetch asset info"));
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+impl<T: ImmichConfig> PhotoSource for ImmichSource<'_, T> {
+    /// Lists today's memories, if enabled, and the assets of the albums and the search.
+    /// The regular sources are only offered when memories are disabled or there are too
+    /// few of them, otherwise they are held back. Assets that are both memories and in the
+    /// regular sources are only listed as memories. A shared link replaces all of them.
+    async fn list_assets(&self) -> anyhow::Result<AssetList> {
+        if let Some(key) = self.config.shared_link_key() {
+            let mut assets = collapse_linked_assets(self.fetch_shared_link_asset_list(key).await?);
+            assets.retain(|asset| self.is_supported_asset(asset));
+            return Ok(AssetList { assets, ..Default::default() });
+        }
+
+        let preferred = match self.config.memories() {
+            Some(memories_config) => {
+                let mut memories = collapse_linked_assets(self.fetch_memories_asset_list(&memories_config).await?);
+                memories.retain(|asset| self.is_supported_asset(asset));
+                let mut seen_ids = std::collections::HashSet::new();
+                memories.retain(|asset| seen_ids.insert(asset.id.clone()));
+                println!("Found {} memories taken on {} in previous years", memories.len(), self.config.today().format("%B %-d"));
+
+                if !self.has_regular_sources() {
+                    return Ok(AssetList { preferred: memories, ..Default::default() });
+                }
+                if memories.len() >= memories_config.min_assets {
+                    // The albums are still listed, so that their images are rotated out for the
+                    // day rather than taken for deleted from Immich
+                    let held_back = self.fetch_regular_asset_list(&memories).await?;
+                    return Ok(AssetList { preferred: memories, assets: Vec::new(), held_back });
+                }
+                println!("Too few memories, filling up with the albums");
+                memories
+            }
+            None => Vec::new(),
+        };
+
+        let assets = self.fetch_regular_asset_list(&preferred).await?;
+        Ok(AssetList { preferred, assets, held_back: Vec::new() })
+    }
+
+    /// Name of the file the asset is stored as in the originals directory,
+    /// in the format "{asset_id}--_--{original_filename}"
+    fn file_name(&self, asset: &Asset) -> String {
+        match self.rendition(asset) {
+            Rendition::Original => format!("{}--_--{}", asset.id, asset.original_file_name),
+            Rendition::Thumbnail(_) => {
+                // Immich thumbnails are JPEG images regardless of the original format
+                let stem = Path::new(&asset.original_file_name)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_else(|| asset.original_file_name.clone());
+                format!("{}--_--{}.jpg", asset.id, stem)
+            }
+        }
+    }
+
+    /// Only originals can be verified, the checksum is not the one of generated thumbnails
+    fn checksum<'a>(&self, asset: &'a Asset) -> Option<&'a str> {
+        match self.rendition(asset) {
+            // Older servers and some asset types come without a checksum
+            Rendition::Original => Some(asset.checksum.as_str()).filter(|checksum| !checksum.is_empty()),
+            Rendition::Thumbnail(_) => None,
+        }
+    }
+
+    /// Immich only reports the size of the originals
+    fn file_size(&self, asset: &Asset) -> Option<u64> {
+        match self.rendition(asset) {
+            Rendition::Original => asset.file_size,
+            Rendition::Thumbnail(_) => None,
+        }
+    }
+
+    async fn download_asset(&self, asset: &Asset, output_path: &str) -> anyhow::Result<()> {
+        let url = match self.rendition(asset) {
+            Rendition::Original => format!("{}/api/assets/{}/original", self.config.immich_url(), asset.id),
+            Rendition::Thumbnail(size) => format!("{}/api/assets/{}/thumbnail?size={}", self.config.immich_url(), asset.id, size),
+        };
+
+        let request = self.authorize(self.client.get(url))
+            .header(header::ACCEPT, "application/octet-stream");
+        let response = rate_limit::send(self.config.rate_limiter(), request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(anyhow::Error::new(HttpStatusError { status, body })
+                .context("Failed to download asset"));
+        }
+
+        save_download(response, &asset.id, self.checksum(asset), output_path, self.config.rate_limiter()).await
+    }
+
+    async fn fetch_metadata(&self, asset: &Asset) -> anyhow::Result<Sidecar> {
+        Ok(Sidecar::from(self.fetch_asset_info(&asset.id).await?))
+    }
+}
+
+/// Leaves out the videos of Live Photos and all but one photo of each stack, the primary one
+/// if it is listed. Each asset left out is linked to the asset that stands for it.
+fn collapse_linked_assets(assets: Vec<Asset>) -> Vec<Asset> {
+    // The asset that stands for each stack
+    let mut stack_heads: HashMap<&str, &str> = HashMap::new();
+    for asset in &assets {
+        if let Some(stack) = &asset.stack {
+            let head = stack_heads.entry(stack.id.as_str()).or_insert(asset.id.as_str());
+            if asset.id == stack.primary_asset_id {
+                *head = asset.id.as_str();
+            }
+        }
+    }
+
+    // The asset each asset to leave out is linked to
+    let mut linked_to: HashMap<String, String> = HashMap::new();
+    for asset in &assets {
+        if let Some(video_id) = &asset.live_photo_video_id {
+            linked_to.insert(video_id.clone(), asset.id.clone());
+        }
+        if let Some(head) = asset.stack.as_ref().map(|stack| stack_heads[stack.id.as_str()]) {
+            if head != asset.id {
+                linked_to.insert(asset.id.clone(), head.to_string());
+            }
+        }
+    }
+
+    // The video of a Live Photo stacked behind another photo belongs to the head of the stack
+    let mut links: HashMap<String, Vec<String>> = HashMap::new();
+    for (linked_id, target_id) in &linked_to {
+        let mut head = target_id;
+        for _ in 0..linked_to.len() {
+            match linked_to.get(head) {
+                Some(next) if next != linked_id => head = next,
+                _ => break,
+            }
+        }
+        links.entry(head.clone()).or_default().push(linked_id.clone());
+    }
+
+    assets
+        .into_iter()
+        .filter(|asset| !linked_to.contains_key(&asset.id))
+        .map(|mut asset| {
+            asset.linked_ids = links.remove(&asset.id).unwrap_or_default();
+            asset.linked_ids.sort();
+            asset
+        })
+        .collect()
+}
+
+/// Start of a day in the local time zone
+fn local_start_of_day(date: chrono::NaiveDate) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+    chrono::Local
+        .from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .map(|start| start.with_timezone(&chrono::Utc))
+}
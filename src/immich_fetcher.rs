@@ -1,86 +1,690 @@
+use actix_web::{web, App, HttpServer};
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use reqwest::Client;
+use serde::Deserialize;
 use std::fs;
 use std::path::Path;
+use std::process::ExitCode;
 use std::time::Duration;
-use std::thread;
 use dotenv::dotenv;
 use image_server_lib::{
+    FetcherConfig,
     ImmichConfig,
+    ImmichSource,
+    LocalFolderSource,
+    MemoriesConfig,
+    MultiSource,
+    PhotoSource,
+    WebDavConfig,
+    WebDavSource,
     fetch_and_download_images,
+    parse_bytes,
+    plan_fetch,
+    report_disk_usage,
+    verify_existing_originals,
+    sync_trigger::{setup_sync, SyncTrigger},
+    FetchPlan,
+    fetcher_state::{AssetStatus, FetcherState},
+    rate_limit::RateLimiter,
+    search::SearchFilter,
+    selection::SelectionStrategy,
+    AssetSize,
+    VideoMode,
 };
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Immich API URL
-    #[arg(long, env("IMMICH_URL"))]
-    immich_url: String,
+    #[arg(long, env("IMMICH_URL"), required_unless_present_any = ["local_dir", "webdav_url", "sources"])]
+    immich_url: Option<String>,
 
     /// Immich API key
-    #[arg(long, env("IMMICH_API_KEY"))]
-    api_key: String,
+    #[arg(long, env("IMMICH_API_KEY"), hide_env_values = true, required_unless_present_any = ["local_dir", "webdav_url", "shared_link_key", "sources"])]
+    api_key: Option<Secret>,
+
+    /// Key of an Immich shared link to take the images from instead of albums, without an API key.
+    /// It is the last part of the link, e.g. https://immich.example.com/share/<key>
+    #[arg(long, env("IMMICH_SHARED_LINK_KEY"), conflicts_with_all = ["api_key", "album_ids", "memories"])]
+    shared_link_key: Option<Secret>,
+
+    /// Password of the shared link, if it is protected by one
+    #[arg(long, env("IMMICH_SHARED_LINK_PASSWORD"), hide_env_values = true, requires = "shared_link_key")]
+    shared_link_password: Option<Secret>,
+
+    /// JSON file listing several Immich servers to take the images from, each with its own
+    /// "name", "url", "apiKey" and "albumIds", or "sharedLinkKey" and "sharedLinkPassword"
+    #[arg(long, env("IMMICH_SOURCES"), conflicts_with_all = ["immich_url", "api_key", "shared_link_key", "album_ids", "local_dir", "webdav_url"])]
+    sources: Option<String>,
+
+    /// Take the images from this directory (e.g. a NAS mount) and its subdirectories instead of Immich
+    #[arg(long, env("LOCAL_PHOTO_DIR"), conflicts_with_all = ["immich_url", "webdav_url"])]
+    local_dir: Option<String>,
+
+    /// Take the images from this WebDAV folder (e.g. in Nextcloud) and its subfolders instead of Immich
+    #[arg(long, env("WEBDAV_URL"), conflicts_with = "immich_url")]
+    webdav_url: Option<String>,
+
+    /// WebDAV user name
+    #[arg(long, env("WEBDAV_USERNAME"))]
+    webdav_username: Option<String>,
+
+    /// WebDAV password, for Nextcloud preferably an app password
+    #[arg(long, env("WEBDAV_PASSWORD"), hide_env_values = true)]
+    webdav_password: Option<Secret>,
+
+    /// Album IDs to fetch images from; repeat the flag or separate IDs with commas.
+    /// Optional when a search is configured.
+    #[arg(long = "album-id", env("IMMICH_ALBUM_ID"), value_delimiter = ',')]
+    album_ids: Vec<String>,
+
+    /// Fetch images matching a search in addition to the albums
+    #[command(flatten)]
+    search: SearchFilter,
+
+    /// Show images taken on today's date in previous years, refreshed at midnight.
+    /// The albums and the search only fill up when there are too few of them.
+    #[arg(long, env("MEMORIES"))]
+    memories: bool,
+
+    /// How many years back to look for images taken on today's date
+    #[arg(long, default_value = "20")]
+    memories_years: u32,
 
-    /// Album ID to fetch images from
-    #[arg(long, env("IMMICH_ALBUM_ID"))]
-    album_id: String,
+    /// With fewer memories than this, the albums and the search fill up the slideshow
+    #[arg(long, default_value = "10")]
+    memories_min: usize,
 
     /// Directory to save original images to
-    #[arg(long, default_value = "originals")]
+    #[arg(long, default_value = "originals", global = true)]
     originals_dir: String,
 
     /// Maximum number of images to fetch
     #[arg(long, default_value = "100")]
     max_images: usize,
+
+    /// How to choose the images to fetch when the albums have more than --max-images
+    #[arg(long, env("SELECTION_STRATEGY"), value_enum, default_value_t = SelectionStrategy::First)]
+    selection: SelectionStrategy,
+
+    /// How long a random or least-recently-shown selection is kept before it is rotated (e.g. 12h)
+    #[arg(long, default_value = "24h", value_parser = humantime::parse_duration)]
+    rotation_period: Duration,
+
+    /// How much more likely a favourite is to be picked by the weighted selection
+    #[arg(long, default_value = "3.0")]
+    favorite_weight: f64,
+
+    /// What to do with videos: skip them or show the still preview Immich generated for them
+    #[arg(long, env("VIDEO_MODE"), value_enum, default_value_t = VideoMode::Skip)]
+    videos: VideoMode,
+
+    /// Also show images archived in Immich. Trashed images and the ones in the locked
+    /// folder are never shown.
+    #[arg(long, env("INCLUDE_ARCHIVED"))]
+    include_archived: bool,
+
+    /// Which size of the images to download: the original file, or an image Immich generated
+    /// from it (preview: sized for screens, fullsize: full resolution JPEG)
+    #[arg(long, env("ASSET_SIZE"), value_enum, default_value_t = AssetSize::Original)]
+    asset_size: AssetSize,
+
+    /// Do not write a JSON sidecar with the capture date, place, camera, people and tags
+    /// of each image next to it
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// How long images that are no longer in the source are kept in the hidden .trash directory of the originals
+    /// directory, from where they come back if they are wanted again (e.g. 12h, 30d, 0s to delete right away)
+    #[arg(long, default_value = "7days", value_parser = humantime::parse_duration)]
+    trash_retention: Duration,
+
+    /// Refuse to remove more than this percentage of the images in one cycle because they are
+    /// missing from the albums, which rather hints at a misconfigured album or a partial response
+    #[arg(long, default_value = "50", value_parser = clap::value_parser!(u32).range(0..=100))]
+    max_removal_percent: u32,
+
+    /// Remove images missing from the albums even if they exceed --max-removal-percent
+    #[arg(long)]
+    force_removal: bool,
+
+    /// How much space the originals, including the trash, may take (e.g. 500MB, 2GB, 1.5GiB).
+    /// The most wanted images according to the selection strategy are kept within it.
+    #[arg(long, env("MAX_ORIGINALS_BYTES"), value_parser = parse_bytes)]
+    max_originals_bytes: Option<u64>,
+
+    /// Maximum number of images to download at the same time
+    #[arg(long, default_value = "4")]
+    download_concurrency: usize,
+
+    /// How many times a failed download is retried within a fetch cycle
+    #[arg(long, default_value = "3")]
+    download_retries: u32,
+
+    /// How long an image that kept failing is left alone before it is attempted again (e.g. 30m, 1h)
+    #[arg(long, default_value = "1h", value_parser = humantime::parse_duration)]
+    failure_cooldown: Duration,
+
+    /// Send at most this many requests per second, to spare a small server (e.g. 2 or 0.5)
+    #[arg(long, env("MAX_REQUESTS_PER_SECOND"))]
+    max_requests_per_second: Option<f64>,
+
+    /// Download at most this many bytes per second (e.g. 2MB or 500KiB)
+    #[arg(long, env("MAX_DOWNLOAD_RATE"), value_parser = parse_bytes)]
+    max_download_rate: Option<u64>,
+
+    /// Shared by all requests, built from --max-requests-per-second and --max-download-rate
+    #[arg(skip)]
+    rate_limiter: RateLimiter,
+
+    /// How often to check the albums for new images (e.g. 30s, 5m, 1h)
+    #[arg(long, env("FETCH_INTERVAL"), default_value = "1m", value_parser = humantime::parse_duration)]
+    interval: Duration,
+
+    /// Only print what a fetch cycle would rename, remove, restore and download, without
+    /// touching the originals directory
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Also write the dry run as JSON to this file, since the log shares the standard output
+    #[arg(long, requires = "dry_run")]
+    dry_run_json: Option<String>,
+
+    /// Run a single fetch cycle and exit. The exit code is 0 on success, 1 if the cycle
+    /// failed and 2 if some images failed to download
+    #[arg(long)]
+    once: bool,
+
+    /// Listen on this address (e.g. 0.0.0.0:8081) for POST /sync requests, which start a fetch
+    /// cycle right away, e.g. from an automation or a phone shortcut
+    #[arg(long, env("SYNC_LISTEN"), conflicts_with_all = ["once", "dry_run"])]
+    sync_listen: Option<String>,
+
+    /// Only accept sync requests that present this token, as a bearer token or in the
+    /// `token` query parameter
+    #[arg(long, env("SYNC_TOKEN"), hide_env_values = true, requires = "sync_listen")]
+    sync_token: Option<Secret>,
+}
+
+/// A key, password or token, shown as "***" when the arguments are printed
+#[derive(Clone)]
+struct Secret(String);
+
+impl std::str::FromStr for Secret {
+    type Err = std::convert::Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Secret(value.to_string()))
+    }
+}
+
+impl std::ops::Deref for Secret {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print which images in the originals directory are synced, which are still pending,
+    /// which failed to download and why images were recently removed
+    Status,
 }
 
 impl ImmichConfig for Args {
     fn immich_url(&self) -> &str {
-        &self.immich_url
+        self.immich_url.as_deref().unwrap_or_default()
     }
 
     fn api_key(&self) -> &str {
-        &self.api_key
+        self.api_key.as_deref().unwrap_or_default()
+    }
+
+    fn album_ids(&self) -> &[String] {
+        &self.album_ids
+    }
+
+    fn shared_link_key(&self) -> Option<&str> {
+        self.shared_link_key.as_deref()
+    }
+
+    fn shared_link_password(&self) -> Option<&str> {
+        self.shared_link_password.as_deref()
+    }
+
+    fn search_filter(&self) -> Option<&SearchFilter> {
+        Some(&self.search)
     }
 
-    fn album_id(&self) -> &str {
-        &self.album_id
+    fn video_mode(&self) -> VideoMode {
+        self.videos
+    }
+
+    fn asset_size(&self) -> AssetSize {
+        self.asset_size
+    }
+
+    fn include_archived(&self) -> bool {
+        self.include_archived
+    }
+
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        Some(&self.rate_limiter)
+    }
+
+    fn memories(&self) -> Option<MemoriesConfig> {
+        self.memories.then_some(MemoriesConfig {
+            years: self.memories_years,
+            min_assets: self.memories_min,
+        })
     }
 }
 
+/// An Immich server in the file given to --sources
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ImmichServer {
+    /// Prefix of the file names of its images, e.g. "grandma"
+    name: String,
+    url: String,
+    #[serde(default)]
+    api_key: Option<String>,
+    #[serde(default)]
+    album_ids: Vec<String>,
+    #[serde(default)]
+    shared_link_key: Option<String>,
+    #[serde(default)]
+    shared_link_password: Option<String>,
+}
+
+/// Reads and checks the servers of the --sources file
+fn load_servers(path: &str, memories: bool) -> Result<Vec<ImmichServer>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read sources file: {}", path))?;
+    let servers: Vec<ImmichServer> = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid sources file: {}", path))?;
+
+    if servers.is_empty() {
+        anyhow::bail!("The sources file {} lists no servers", path);
+    }
+    for server in &servers {
+        if server.shared_link_key.is_some() {
+            if server.api_key.is_some() || !server.album_ids.is_empty() {
+                anyhow::bail!("Server {} has a shared link, which replaces the API key and the albums", server.name);
+            }
+        } else if server.api_key.is_none() {
+            anyhow::bail!("Server {} needs an apiKey or a sharedLinkKey", server.name);
+        } else if server.album_ids.is_empty() && !memories {
+            anyhow::bail!("Server {} needs albumIds unless --memories is used", server.name);
+        }
+    }
+    Ok(servers)
+}
+
+/// One of the servers of the --sources file: its own URL, credentials and albums,
+/// everything else from the command line
+struct ServerArgs<'a> {
+    server: &'a ImmichServer,
+    args: &'a Args,
+}
+
+impl ImmichConfig for ServerArgs<'_> {
+    fn immich_url(&self) -> &str {
+        &self.server.url
+    }
+
+    fn api_key(&self) -> &str {
+        self.server.api_key.as_deref().unwrap_or_default()
+    }
+
+    fn album_ids(&self) -> &[String] {
+        &self.server.album_ids
+    }
+
+    fn shared_link_key(&self) -> Option<&str> {
+        self.server.shared_link_key.as_deref()
+    }
+
+    fn shared_link_password(&self) -> Option<&str> {
+        self.server.shared_link_password.as_deref()
+    }
+
+    fn video_mode(&self) -> VideoMode {
+        self.args.video_mode()
+    }
+
+    fn asset_size(&self) -> AssetSize {
+        self.args.asset_size()
+    }
+
+    fn include_archived(&self) -> bool {
+        self.args.include_archived()
+    }
+
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        Some(&self.args.rate_limiter)
+    }
+
+    fn memories(&self) -> Option<MemoriesConfig> {
+        // A shared link cannot search for memories
+        self.server.shared_link_key.is_none().then(|| self.args.memories()).flatten()
+    }
+}
+
+impl WebDavConfig for Args {
+    fn webdav_url(&self) -> &str {
+        self.webdav_url.as_deref().unwrap_or_default()
+    }
+
+    fn webdav_username(&self) -> Option<&str> {
+        self.webdav_username.as_deref()
+    }
+
+    fn webdav_password(&self) -> Option<&str> {
+        self.webdav_password.as_deref()
+    }
+
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        Some(&self.rate_limiter)
+    }
+}
+
+impl FetcherConfig for Args {
+    fn download_concurrency(&self) -> usize {
+        self.download_concurrency
+    }
+
+    fn download_retries(&self) -> u32 {
+        self.download_retries
+    }
+
+    fn failure_cooldown(&self) -> Duration {
+        self.failure_cooldown
+    }
+
+    fn selection_strategy(&self) -> SelectionStrategy {
+        self.selection
+    }
+
+    fn rotation_period(&self) -> Duration {
+        self.rotation_period
+    }
+
+    fn favorite_weight(&self) -> f64 {
+        self.favorite_weight
+    }
+
+    fn metadata_sidecars(&self) -> bool {
+        !self.no_metadata
+    }
+
+    fn trash_retention(&self) -> Duration {
+        self.trash_retention
+    }
+
+    fn max_removal_percent(&self) -> Option<u32> {
+        (!self.force_removal).then_some(self.max_removal_percent)
+    }
+
+    fn max_originals_bytes(&self) -> Option<u64> {
+        self.max_originals_bytes
+    }
+}
+
+/// Exit code of a `--once` run in which some images failed to download
+const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+/// Exit code of a `--once` run that kept images because removing them exceeded the limit
+const EXIT_REMOVAL_REFUSED: u8 = 3;
+
+/// Resolves when the fetcher is asked to stop with SIGINT or SIGTERM
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .context("Failed to listen for SIGTERM")?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.context("Failed to listen for SIGINT")?,
+            _ = sigterm.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.context("Failed to listen for Ctrl+C")?;
+
+    Ok(())
+}
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     // Load environment variables from .env file if present
     dotenv().ok();
     
     // Parse command line arguments
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(Command::Status) = args.command {
+        print_status(&args.originals_dir)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let servers = match &args.sources {
+        Some(path) if args.search.is_configured() => anyhow::bail!("A search is specific to one server and cannot be combined with --sources {}", path),
+        Some(path) => load_servers(path, args.memories)?,
+        None => Vec::new(),
+    };
+
+    let uses_immich = args.local_dir.is_none() && args.webdav_url.is_none() && args.shared_link_key.is_none() && args.sources.is_none();
+    if uses_immich && args.album_ids.is_empty() && !args.search.is_configured() && !args.memories {
+        anyhow::bail!("Specify at least one --album-id, a search (e.g. --search-query or --search-person-id), --memories or --shared-link-key");
+    }
     
     // Create directories if they don't exist
-    if !Path::new(&args.originals_dir).exists() {
+    if !args.dry_run && !Path::new(&args.originals_dir).exists() {
         fs::create_dir_all(&args.originals_dir)
             .context("Failed to create originals directory")?;
     }
     
+    args.rate_limiter = RateLimiter::new(args.max_requests_per_second, args.max_download_rate);
+
     // Initialize HTTP client
     let client = Client::builder()
         .timeout(Duration::from_secs(30))
         .build()?;
 
-    println!("Starting continuous fetcher service");
+    if args.dry_run {
+        println!("Dry run, the originals directory is left untouched");
+    } else if args.once {
+        println!("Running a single fetch cycle");
+    } else {
+        println!("Starting continuous fetcher service");
+    }
     println!("Args: {:?}", args);
-    println!("Will check for new images every minute");
-    
-    // Run continuously
+
+    if let Some(local_dir) = &args.local_dir {
+        run(&LocalFolderSource::new(local_dir), &args).await
+    } else if args.webdav_url.is_some() {
+        run(&WebDavSource::new(&client, &args), &args).await
+    } else if !servers.is_empty() {
+        let configs: Vec<ServerArgs> = servers
+            .iter()
+            .map(|server| ServerArgs { server, args: &args })
+            .collect();
+        let source = MultiSource::new(configs
+            .iter()
+            .map(|config| (config.server.name.clone(), ImmichSource::new(&client, config)))
+            .collect())?;
+        run(&source, &args).await
+    } else {
+        run(&ImmichSource::new(&client, &args), &args).await
+    }
+}
+
+/// Prints the sync manifest kept in the originals directory
+fn print_status(originals_dir: &str) -> Result<()> {
+    let state = FetcherState::load(originals_dir)?;
+
+    for (status, heading) in [
+        (AssetStatus::Synced, "Synced"),
+        (AssetStatus::Pending, "Pending"),
+        (AssetStatus::Failed, "Failed"),
+    ] {
+        let entries: Vec<_> = state.assets
+            .iter()
+            .filter(|(asset_id, entry)| state.asset_status(asset_id, entry, originals_dir) == status)
+            .collect();
+        println!("{} ({}):", heading, entries.len());
+
+        for (asset_id, entry) in entries {
+            let origin = entry.origin.as_deref().unwrap_or("unknown source");
+            let downloaded = entry.downloaded_at.map_or("unknown".to_string(), |at| at.to_rfc3339());
+            println!("  {} from {}, downloaded {}, last seen {}", entry.file_name, origin, downloaded, entry.last_seen.to_rfc3339());
+            if !entry.linked_assets.is_empty() {
+                println!("    also stands for {}", entry.linked_assets.join(", "));
+            }
+            if let Some(failed) = state.failed.get(asset_id) {
+                println!("    failed {} times, not retrying before {}: {}", failed.failures, failed.retry_after.to_rfc3339(), failed.last_error);
+            }
+        }
+    }
+
+    println!("Removed ({}):", state.removed.len());
+    for (file_name, removed) in &state.removed {
+        println!("  {} at {}: {}", file_name, removed.removed_at.to_rfc3339(), removed.reason);
+    }
+    report_disk_usage(originals_dir, None)?;
+
+    Ok(())
+}
+
+/// Prints what a fetch cycle would do
+fn print_plan(plan: &FetchPlan) {
+    if plan.unchanged {
+        println!("The selected images are unchanged and already in place, nothing to do");
+        return;
+    }
+    if let Some(refused) = &plan.removal_refused {
+        println!("The removals would be skipped: {}", refused);
+    }
+    for rename in &plan.renames {
+        println!("Rename {} to {}", rename.from, rename.to);
+    }
+    let action = if plan.removal_refused.is_some() { "Keep" } else { "Remove" };
+    for removal in &plan.removals {
+        println!("{} {}: {}", action, removal.file_name, removal.reason);
+    }
+    for restore in &plan.restores {
+        println!("Restore {} from the trash", restore.file_name);
+    }
+    for download in &plan.downloads {
+        for file_name in download.sidecar.iter().chain(&download.image) {
+            println!("Download {}", file_name);
+        }
+    }
+    for asset_id in &plan.cooling_down {
+        println!("Skip asset {}, it failed recently", asset_id);
+    }
+    println!("{} downloads, {} removals, {} restores, {} renames",
+             plan.downloads.len(), plan.removals.len(), plan.restores.len(), plan.renames.len());
+}
+
+/// Runs the fetcher with the given photo source
+async fn run<S: PhotoSource>(source: &S, args: &Args) -> Result<ExitCode> {
+    if args.dry_run {
+        let plan = plan_fetch(source, args, &args.originals_dir, args.max_images).await?;
+        print_plan(&plan);
+        if let Some(path) = &args.dry_run_json {
+            fs::write(path, serde_json::to_string_pretty(&plan)?)
+                .with_context(|| format!("Failed to write dry run: {}", path))?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Make sure no corrupt download from a previous run is kept forever
+    match verify_existing_originals(source, &args.originals_dir).await {
+        Ok(0) => println!("All existing originals match their checksums"),
+        Ok(removed) => println!("Removed {} corrupt originals, they will be fetched again", removed),
+        Err(e) => eprintln!("Error verifying existing originals: {:#}", e),
+    }
+
+    if args.once {
+        return Ok(match fetch_and_download_images(source, args, &args.originals_dir, args.max_images).await {
+            Ok(summary) if summary.removal_refused.is_some() => {
+                eprintln!("Fetch cycle completed without removing any images");
+                ExitCode::from(EXIT_REMOVAL_REFUSED)
+            }
+            Ok(summary) if summary.failed.is_empty() => {
+                println!("Fetch cycle completed successfully");
+                ExitCode::SUCCESS
+            }
+            Ok(summary) => {
+                eprintln!("Fetch cycle completed, {} images failed to download", summary.failed.len());
+                ExitCode::from(EXIT_PARTIAL_FAILURE)
+            }
+            Err(e) => {
+                eprintln!("Error during fetch cycle: {:#}", e);
+                ExitCode::FAILURE
+            }
+        });
+    }
+
+    let trigger = web::Data::new(SyncTrigger::new(args.sync_token.as_deref().map(str::to_string)));
+    if let Some(address) = &args.sync_listen {
+        let app_trigger = trigger.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_trigger.clone())
+                .configure(setup_sync)
+        })
+        .workers(1)
+        // The fetcher handles the signals itself
+        .disable_signals()
+        .bind(address)
+        .with_context(|| format!("Failed to listen for sync requests on {}", address))?
+        .run();
+        tokio::spawn(server);
+        println!("Listening for sync requests on http://{}/sync", address);
+    }
+
+    println!("Will check for new images every {}", humantime::format_duration(args.interval));
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    // Run continuously until asked to stop. Downloads are atomic, so interrupting
+    // a fetch cycle never leaves a partial original behind.
     loop {
-        match fetch_and_download_images(&client, &args, &args.originals_dir, args.max_images).await {
-            Ok(_) => println!("Fetch cycle completed successfully"),
-            Err(e) => eprintln!("Error during fetch cycle: {}", e),
+        tokio::select! {
+            result = fetch_and_download_images(source, args, &args.originals_dir, args.max_images) => {
+                match result {
+                    Ok(summary) if summary.removal_refused.is_some() => println!("Fetch cycle completed without removing any images"),
+                    Ok(summary) if summary.failed.is_empty() => println!("Fetch cycle completed successfully"),
+                    Ok(summary) => println!("Fetch cycle completed, {} images failed to download", summary.failed.len()),
+                    Err(e) => eprintln!("Error during fetch cycle: {:#}", e),
+                }
+            }
+            result = &mut shutdown => break result?,
+        }
+
+        println!("Waiting {} before next fetch...", humantime::format_duration(args.interval));
+        tokio::select! {
+            _ = tokio::time::sleep(args.interval) => {}
+            // Requests that came in during the cycle start the next one right away
+            _ = trigger.requested() => println!("Sync requested, fetching now"),
+            result = &mut shutdown => break result?,
         }
-        
-        // Wait for 1 minute before the next fetch
-        println!("Waiting 60 seconds before next fetch...");
-        thread::sleep(Duration::from_secs(60));
     }
+
+    println!("Shutting down fetcher service");
+    Ok(ExitCode::SUCCESS)
 }
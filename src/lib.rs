@@ -1,145 +1,1164 @@
 use anyhow::Context;
-use reqwest::{header, Client};
+use base64::Engine;
+use fetcher_state::FetcherState;
+use metadata::Sidecar;
+use rate_limit::RateLimiter;
+use selection::{SelectionConfig, SelectionStrategy};
+use futures_util::StreamExt;
+use rand::Rng;
+use reqwest::StatusCode;
 
 use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
 use std::path::Path;
 use std::fs;
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 
 pub mod server_lib;
+pub mod fetcher_state;
+pub mod selection;
+pub mod metadata;
+pub mod search;
+pub mod immich;
+pub mod local_source;
+pub mod webdav;
+pub mod multi_source;
+pub mod sync_trigger;
+pub mod rate_limit;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct AlbumResponse {
-    pub assets: Vec<Asset>,
-}
+pub use immich::{AssetSize, ImmichConfig, ImmichSource, MemoriesConfig, VideoMode};
+pub use local_source::LocalFolderSource;
+pub use multi_source::MultiSource;
+pub use webdav::{WebDavConfig, WebDavSource};
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Asset {
+/// A photo or video offered by a photo source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Asset {
+    /// Identifies the asset within its source, must be usable in a file name
     pub id: String,
+    /// "IMAGE", "VIDEO" or another type of asset
     #[serde(rename = "type")]
     pub asset_type: String,
+    /// Base64 encoded SHA-1 of the original file, empty if the source does not know it
+    #[serde(default)]
     pub checksum: String,
     #[serde(rename = "originalFileName")]
     pub original_file_name: String,
+    #[serde(rename = "fileCreatedAt", default)]
+    pub file_created_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "isFavorite", default)]
+    pub is_favorite: bool,
+    /// Whether the asset was moved to the trash of the source and is about to be deleted
+    #[serde(rename = "isTrashed", default)]
+    pub is_trashed: bool,
+    /// Whether the asset was archived, which hides it from the timeline of older Immich versions
+    #[serde(rename = "isArchived", default)]
+    pub is_archived: bool,
+    /// "timeline", "archive", "hidden" or "locked" in newer Immich versions
+    #[serde(default)]
+    pub visibility: Option<String>,
+    /// ID of the video part of a Live Photo, which is a separate asset
+    #[serde(rename = "livePhotoVideoId", default)]
+    pub live_photo_video_id: Option<String>,
+    /// The stack the asset is in, e.g. the photos of a burst
+    #[serde(default, deserialize_with = "stack_from_value")]
+    pub stack: Option<AssetStack>,
+    /// IDs of the assets this one stands for, e.g. the video of a Live Photo or the other
+    /// photos of a stack, recorded in the sync manifest
+    #[serde(skip)]
+    pub linked_ids: Vec<String>,
+    /// Where the source found the asset, e.g. the album it is in, recorded in the sync manifest
+    #[serde(skip)]
+    pub origin: Option<String>,
+    /// Size of the original file in bytes, if the source knows it
+    #[serde(rename = "exifInfo", default, deserialize_with = "file_size_from_exif", skip_serializing)]
+    pub file_size: Option<u64>,
+}
+
+/// A stack of assets, of which only the primary one is shown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetStack {
+    pub id: String,
+    #[serde(rename = "primaryAssetId")]
+    pub primary_asset_id: String,
+}
+
+/// Reads the stack of an asset, ignoring the list of stacked assets older Immich versions return
+fn stack_from_value<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<AssetStack>, D::Error> {
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| serde_json::from_value(value).ok()))
+}
+
+/// Reads the file size from the EXIF information Immich includes with an asset
+fn file_size_from_exif<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    struct ExifFileSize {
+        #[serde(rename = "fileSizeInByte")]
+        file_size_in_byte: Option<u64>,
+    }
+
+    let exif = Option::<ExifFileSize>::deserialize(deserializer)?;
+    Ok(exif.and_then(|exif| exif.file_size_in_byte))
+}
+
+/// The assets a source offers in a fetch cycle
+#[derive(Debug, Default)]
+pub struct AssetList {
+    /// Assets that go first, e.g. today's memories
+    pub preferred: Vec<Asset>,
+    /// Assets that fill up the room left by the preferred ones
+    pub assets: Vec<Asset>,
+    /// Assets the source has but does not offer this cycle, e.g. the albums on a day with
+    /// enough memories. Their files are removed as rotated out of the selection.
+    pub held_back: Vec<Asset>,
+}
+
+/// Where the fetcher gets the photos from, e.g. an Immich server or a local folder
+#[allow(async_fn_in_trait)]
+pub trait PhotoSource {
+    /// Lists the assets that can be shown, leaving out the ones that cannot.
+    /// An asset missing from the list is removed from the originals directory.
+    async fn list_assets(&self) -> anyhow::Result<AssetList>;
+
+    /// Name of the file the asset is stored as in the originals directory,
+    /// in the format "{asset_id}--_--{file_name}"
+    fn file_name(&self, asset: &Asset) -> String;
+
+    /// The base64 encoded SHA-1 the downloaded file must have, if the source knows it
+    fn checksum<'a>(&self, asset: &'a Asset) -> Option<&'a str>;
+
+    /// Size of the downloaded file in bytes, if the source knows it
+    fn file_size(&self, asset: &Asset) -> Option<u64> {
+        asset.file_size
+    }
+
+    /// Writes the asset to `output_path` atomically, so the transformer never sees a partial file
+    async fn download_asset(&self, asset: &Asset, output_path: &str) -> anyhow::Result<()>;
+
+    /// Gets the metadata written into the sidecar of the asset
+    async fn fetch_metadata(&self, asset: &Asset) -> anyhow::Result<Sidecar>;
+}
+
+/// Settings of the fetcher that apply to every photo source
+pub trait FetcherConfig {
+    /// Maximum number of assets downloaded at the same time
+    fn download_concurrency(&self) -> usize {
+        1
+    }
+
+    /// How many times a failed download is retried within a fetch cycle
+    fn download_retries(&self) -> u32 {
+        3
+    }
+
+    /// Delay before the first retry, doubled for every further retry
+    fn retry_base_delay(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// How long an asset that kept failing is left alone before it is attempted again
+    fn failure_cooldown(&self) -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    /// How to choose the assets to keep when the source has more than `max_images`
+    fn selection_strategy(&self) -> SelectionStrategy {
+        SelectionStrategy::First
+    }
+
+    /// How long a rotating selection is kept before the next one is drawn
+    fn rotation_period(&self) -> Duration {
+        Duration::from_secs(24 * 60 * 60)
+    }
+
+    /// How much more likely a favourite is to be picked by the weighted strategy
+    fn favorite_weight(&self) -> f64 {
+        3.0
+    }
+
+    /// Whether to write a JSON sidecar with the asset metadata next to each image
+    fn metadata_sidecars(&self) -> bool {
+        false
+    }
+
+    /// How long files no longer in the source are kept in the trash of the originals directory, from where
+    /// they are restored instead of downloaded again when they are wanted back.
+    /// Zero removes them right away.
+    fn trash_retention(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// The largest share of the assets in the originals directory, in percent, that may be
+    /// removed in one cycle because they are missing from the source, or None for no limit
+    fn max_removal_percent(&self) -> Option<u32> {
+        None
+    }
+
+    /// How many bytes the originals directory may use, including the trash, or None for no limit
+    fn max_originals_bytes(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Name of the metadata sidecar of an image: the image file name with a `.json` extension,
+/// so the transformer's output image and sidecar share the same stem
+fn sidecar_file_name(image_file_name: &str) -> String {
+    let stem = Path::new(image_file_name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| image_file_name.to_string());
+    format!("{}.json", stem)
+}
+
+/// How many bytes the files of an asset take in the originals directory: the actual size of
+/// the files in place or in the trash, otherwise the size the source reports, if any, and
+/// `unknown_size` for an image of unknown size
+fn expected_size<S: PhotoSource, C: FetcherConfig>(source: &S, args: &C, originals_dir: &str, asset: &Asset, unknown_size: u64) -> u64 {
+    let file_size = |file_name: &str| {
+        [Path::new(originals_dir).join(file_name), Path::new(originals_dir).join(TRASH_DIR_NAME).join(file_name)]
+            .iter()
+            .find_map(|path| fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+    };
+
+    let image_file_name = source.file_name(asset);
+    let image_size = file_size(&image_file_name)
+        .or_else(|| source.file_size(asset))
+        .unwrap_or(unknown_size);
+    let sidecar_size = if args.metadata_sidecars() {
+        file_size(&sidecar_file_name(&image_file_name)).unwrap_or(0)
+    } else {
+        0
+    };
+    image_size + sidecar_size
+}
+
+/// Average size of the images in the originals directory, the best guess for an image of
+/// unknown size such as a preview. Zero while there are none yet.
+fn average_image_size(originals_dir: &str) -> u64 {
+    let sizes: Vec<u64> = fs::read_dir(originals_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            !name.starts_with('.') && !name.ends_with(".json")
+        })
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .collect();
+    match sizes.len() {
+        0 => 0,
+        count => sizes.iter().sum::<u64>() / count as u64,
+    }
+}
+
+/// Total size of the files directly in `dir`, zero if it does not exist. Hidden files such as
+/// the fetcher state and partial downloads are not counted.
+fn directory_size(dir: &Path) -> anyhow::Result<u64> {
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory: {:?}", dir))? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() && !is_hidden(&entry.path()) {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Formats a number of bytes for the log, e.g. "1.5 GB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next_unit in UNITS {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next_unit;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+/// Parses a size in bytes such as "500MB", "2GB" or "1.5GiB"
+pub fn parse_bytes(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("Invalid size: {}", size))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000 * 1000,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "t" | "tb" => 1000 * 1000 * 1000 * 1000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(format!("Unknown size unit in {}, use e.g. MB or GB", size)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Fingerprint of the files of a working set, given as the expected file names of each asset
+fn working_set_fingerprint(file_names: &std::collections::HashMap<String, Vec<String>>) -> String {
+    let mut names: Vec<&String> = file_names.values().flatten().collect();
+    names.sort();
+
+    let mut hasher = Sha1::new();
+    for name in names {
+        hasher.update(name.as_bytes());
+        hasher.update(b"\n");
+    }
+    base64::engine::general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+/// Error for an unsuccessful HTTP response from Immich
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Tells whether an error is worth retrying: network and IO errors, server errors and
+/// truncated downloads are, while client errors such as a missing asset are not
+fn is_transient(error: &anyhow::Error) -> bool {
+    match error.chain().find_map(|e| e.downcast_ref::<HttpStatusError>()) {
+        Some(http_error) => {
+            http_error.status.is_server_error()
+                || http_error.status == StatusCode::TOO_MANY_REQUESTS
+                || http_error.status == StatusCode::REQUEST_TIMEOUT
+        }
+        None => true,
+    }
 }
 
-// Trait to abstract the API configuration
-pub trait ImmichConfig {
-    fn immich_url(&self) -> &str;
-    fn api_key(&self) -> &str;
-    fn album_id(&self) -> &str;
+/// Exponential backoff with jitter: a random delay between half and all of `base * 2^attempt`
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    const MAX_DELAY: Duration = Duration::from_secs(60);
+    let delay = base.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_DELAY);
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// Outcome of a single fetch cycle
+#[derive(Debug, Default)]
+pub struct FetchSummary {
+    pub downloaded: usize,
+    pub skipped: usize,
+    pub removed: usize,
+    /// Assets not attempted because they failed recently and are cooling down
+    pub cooling_down: usize,
+    /// Asset IDs that failed to download together with the error
+    pub failed: Vec<(String, String)>,
+    /// Why the removals were skipped, if they exceeded the removal limit
+    pub removal_refused: Option<String>,
+}
+
+/// Decodes an Immich checksum, which is the base64 encoded SHA-1 of the original file
+fn decode_checksum(checksum: &str) -> anyhow::Result<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(checksum)
+        .with_context(|| format!("Invalid asset checksum: {}", checksum))
+}
+
+/// Computes the SHA-1 of a file on disk without loading it into memory
+fn file_sha1(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut file = fs::File::open(path)
+        .with_context(|| format!("Failed to open file: {:?}", path))?;
+    let mut hasher = Sha1::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read file: {:?}", path))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Runs `operation`, retrying transient failures with exponential backoff.
+/// `description` names the operation in the log, e.g. "Download of asset X".
+async fn with_retry<C, F, Fut>(config: &C, description: &str, mut operation: F) -> anyhow::Result<()>
+where
+    C: FetcherConfig,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < config.download_retries() && is_transient(&e) => {
+                let delay = backoff_delay(config.retry_base_delay(), attempt);
+                eprintln!("{} failed: {:#}. Retrying in {:?}", description, e, delay);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
 }
 
-async fn fetch_album_asset_list<T: ImmichConfig>(client: &Client, config: &T) -> anyhow::Result<Vec<Asset>> {
-    let url = format!("{}/api/albums/{}?withoutAssets=false",
-                      config.immich_url(), config.album_id());
+/// Writes the metadata sidecar of an asset, atomically like the downloads
+async fn write_sidecar<S: PhotoSource>(source: &S, asset: &Asset, output_path: &str) -> anyhow::Result<()> {
+    let sidecar = source.fetch_metadata(asset).await?;
 
-    let response = client.get(url)
-        .header(header::ACCEPT, "application/json")
-        .header("x-api-key", config.api_key())
-        .send()
-        .await?;
+    let temp_path = temp_download_path(output_path)?;
+    tokio::fs::write(&temp_path, serde_json::to_vec_pretty(&sidecar)?).await
+        .with_context(|| format!("Failed to write temporary file: {}", temp_path))?;
+    tokio::fs::rename(&temp_path, output_path).await
+        .with_context(|| format!("Failed to move sidecar into place: {}", output_path))?;
+    Ok(())
+}
+
+/// An asset of the working set with the files that are still missing for it
+struct PendingAsset<'a> {
+    asset: &'a Asset,
+    /// Where to download the image to, unless it already exists
+    image_path: Option<String>,
+    /// Where to write the metadata sidecar to, unless it already exists or is disabled
+    sidecar_path: Option<String>,
+}
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().await?;
-        anyhow::bail!("Failed to fetch album assets: HTTP {}: {}", status, text);
+/// Writes the missing files of an asset. The sidecar goes first, so it is already
+/// in place when the transformer picks up the image.
+async fn fetch_pending_asset<S: PhotoSource, C: FetcherConfig>(source: &S, config: &C, pending: &PendingAsset<'_>) -> anyhow::Result<()> {
+    let asset = pending.asset;
+    if let Some(sidecar_path) = &pending.sidecar_path {
+        with_retry(config, &format!("Fetching metadata of asset {}", asset.id), || {
+            write_sidecar(source, asset, sidecar_path)
+        }).await?;
     }
+    if let Some(image_path) = &pending.image_path {
+        with_retry(config, &format!("Download of asset {}", asset.id), || {
+            source.download_asset(asset, image_path)
+        }).await?;
+    }
+    Ok(())
+}
 
-    let resp: AlbumResponse = response.json().await?;
-    Ok(resp.assets)
+/// Extensions of the image files picked up from folders, when there is nothing better to go by
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff", "heic", "heif", "avif",
+];
+
+/// Tells whether the file name has the extension of an image
+fn has_image_extension(file_name: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
 }
 
-async fn download_asset<T: ImmichConfig>(client: &Client, config: &T, asset_id: &str, output_path: &str) -> anyhow::Result<()> {
-    let url = format!("{}/api/assets/{}/original", config.immich_url(), asset_id);
+/// An image found in a folder, whether local or on a WebDAV server. The ID is derived from
+/// `identity`, which must change whenever the file does, so a replaced photo is fetched again
+/// while the stale copy is removed.
+fn file_asset(
+    identity: &[&[u8]],
+    original_file_name: String,
+    file_created_at: Option<chrono::DateTime<chrono::Utc>>,
+    file_size: Option<u64>,
+) -> Asset {
+    let mut hasher = Sha1::new();
+    for part in identity {
+        hasher.update(part);
+    }
+    let id: String = hasher.finalize()[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
 
-    let response = client.get(url)
-        .header(header::ACCEPT, "application/octet-stream")
-        .header("x-api-key", config.api_key())
-        .send()
-        .await?;
+    Asset {
+        id,
+        asset_type: "IMAGE".to_string(),
+        checksum: String::new(),
+        original_file_name,
+        file_created_at,
+        is_favorite: false,
+        is_trashed: false,
+        is_archived: false,
+        visibility: None,
+        live_photo_video_id: None,
+        stack: None,
+        linked_ids: Vec::new(),
+        origin: None,
+        file_size,
+    }
+}
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().await?;
-        anyhow::bail!("Failed to download asset: HTTP {}: {}", status, text);
+/// The metadata of an image found in a folder: just its name and date
+fn file_sidecar(asset: &Asset) -> Sidecar {
+    Sidecar {
+        id: asset.id.clone(),
+        original_file_name: asset.original_file_name.clone(),
+        captured_at: asset.file_created_at,
+        ..Default::default()
     }
+}
+
+/// Get the hidden temporary path an asset is downloaded to before it is moved to `output_path`
+fn temp_download_path(output_path: &str) -> anyhow::Result<String> {
+    let path = Path::new(output_path);
+    let file_name = path.file_name()
+        .context("Invalid output path")?
+        .to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.part", file_name));
+    Ok(temp_path.to_string_lossy().to_string())
+}
 
-    let bytes = response.bytes().await?;
-    fs::write(output_path, bytes)?;
+/// Writes the response body to `temp_path` chunk by chunk, within the bandwidth of the limiter,
+/// and verifies it against the asset checksum, if any
+async fn write_verified_download(
+    mut response: reqwest::Response,
+    asset_id: &str,
+    checksum: Option<&str>,
+    temp_path: &str,
+    limiter: Option<&RateLimiter>,
+) -> anyhow::Result<()> {
+    let mut file = tokio::fs::File::create(temp_path).await
+        .with_context(|| format!("Failed to create temporary file: {}", temp_path))?;
+    let mut hasher = Sha1::new();
+
+    while let Some(chunk) = response.chunk().await? {
+        if let Some(limiter) = limiter {
+            limiter.wait_for_bytes(chunk.len()).await;
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await
+            .with_context(|| format!("Failed to write temporary file: {}", temp_path))?;
+    }
+
+    // Never store a truncated or corrupted download, it would be skipped as existing forever
+    if let Some(checksum) = checksum {
+        let expected = decode_checksum(checksum)?;
+        let actual = hasher.finalize();
+        if actual.as_slice() != expected.as_slice() {
+            anyhow::bail!("Checksum mismatch for asset {}: expected {}, got {}",
+                          asset_id,
+                          checksum,
+                          base64::engine::general_purpose::STANDARD.encode(actual));
+        }
+    }
+
+    file.sync_all().await
+        .with_context(|| format!("Failed to sync temporary file: {}", temp_path))?;
 
     Ok(())
 }
 
-pub async fn fetch_and_download_images<T: ImmichConfig>(
-    client: &Client,
-    args: &T,
-    originals_dir: &str,
-    max_images: usize
+/// Streams the response into a hidden temporary file next to `output_path` and renames it
+/// into place once complete and verified, so the transformer never sees a partial original
+async fn save_download(
+    response: reqwest::Response,
+    asset_id: &str,
+    checksum: Option<&str>,
+    output_path: &str,
+    limiter: Option<&RateLimiter>,
 ) -> anyhow::Result<()> {
-    // Fetch assets from album
-    let assets = fetch_album_asset_list(client, args).await?;
-    println!("Found {} assets in album", assets.len());
+    let temp_path = temp_download_path(output_path)?;
+    if let Err(e) = write_verified_download(response, asset_id, checksum, &temp_path, limiter).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(e);
+    }
+    tokio::fs::rename(&temp_path, output_path).await
+        .with_context(|| format!("Failed to move download into place: {}", output_path))?;
+    Ok(())
+}
+
+/// The assets chosen for the originals directory in a fetch cycle
+struct WorkingSet<'a> {
+    selected: Vec<&'a Asset>,
+    /// Expected file names of each selected asset (the image and its sidecar), keyed by asset ID
+    file_names: std::collections::HashMap<String, Vec<String>>,
+    /// IDs of all assets the source listed, selected or not
+    listed_ids: std::collections::HashSet<String>,
+    /// IDs of the assets the source listed as preferred, e.g. memories
+    preferred_ids: std::collections::HashSet<&'a str>,
+    /// Expected size of the files of the selected assets, only counted with a disk budget
+    bytes: u64,
+    /// Size assumed for an image of unknown size, only estimated with a disk budget
+    unknown_size: u64,
+    /// ID of the listed asset that stands for each asset linked to it, e.g. the video of
+    /// a Live Photo or another photo of a stack, keyed by the ID of the linked asset
+    linked_to: std::collections::HashMap<String, String>,
+}
+
+/// Chooses the working set: all assets when they fit, otherwise according to the strategy.
+/// Preferred assets such as memories go first, the others only fill up the remaining room.
+fn choose_working_set<'a, S: PhotoSource, C: FetcherConfig>(
+    source: &S,
+    args: &C,
+    originals_dir: &str,
+    list: &'a AssetList,
+    state: &FetcherState,
+    now: chrono::DateTime<chrono::Utc>,
+    max_images: usize,
+) -> WorkingSet<'a> {
+    let selection_config = SelectionConfig {
+        strategy: args.selection_strategy(),
+        rotation_period: args.rotation_period(),
+        favorite_weight: args.favorite_weight(),
+    };
+    let ranked = selection::rank_assets(&list.preferred, &selection_config, state, now)
+        .into_iter()
+        .chain(selection::rank_assets(&list.assets, &selection_config, state, now));
+
+    // Within the disk budget, keep the most wanted assets that fit. An asset too large
+    // for the room that is left is passed over for smaller, less wanted ones.
+    let mut selected: Vec<&Asset> = Vec::new();
+    let mut used_bytes = 0;
+    let unknown_size = if args.max_originals_bytes().is_some() { average_image_size(originals_dir) } else { 0 };
+    for asset in ranked {
+        if selected.len() >= max_images {
+            break;
+        }
+        if let Some(max_bytes) = args.max_originals_bytes() {
+            let size = expected_size(source, args, originals_dir, asset, unknown_size);
+            if used_bytes + size > max_bytes {
+                continue;
+            }
+            used_bytes += size;
+        }
+        selected.push(asset);
+    }
 
-    // Create a set of current asset IDs for quick lookup
-    let current_asset_ids: std::collections::HashSet<String> = assets
+    let file_names = selected
         .iter()
-        .take(max_images)
+        .map(|asset| {
+            let image_file_name = source.file_name(asset);
+            let mut file_names = Vec::new();
+            if args.metadata_sidecars() {
+                file_names.push(sidecar_file_name(&image_file_name));
+            }
+            file_names.push(image_file_name);
+            (asset.id.clone(), file_names)
+        })
+        .collect();
+    let listed_ids = list.preferred
+        .iter()
+        .chain(&list.assets)
+        .chain(&list.held_back)
         .map(|asset| asset.id.clone())
         .collect();
+    let preferred_ids = list.preferred
+        .iter()
+        .map(|asset| asset.id.as_str())
+        .collect();
+
+    let linked_to = list.preferred
+        .iter()
+        .chain(&list.assets)
+        .chain(&list.held_back)
+        .flat_map(|asset| asset.linked_ids.iter().map(|linked_id| (linked_id.clone(), asset.id.clone())))
+        .collect();
+
+    WorkingSet { selected, file_names, listed_ids, preferred_ids, bytes: used_bytes, unknown_size, linked_to }
+}
+
+/// What a fetch cycle changes in the originals directory
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchPlan {
+    /// The working set is the same as after the last complete cycle, so nothing changes
+    pub unchanged: bool,
+    /// Images renamed in the source that keep their file under the new name
+    pub renames: Vec<PlannedRename>,
+    /// Files that are no longer wanted
+    pub removals: Vec<PlannedRemoval>,
+    /// Files brought back from the trash instead of being downloaded again
+    pub restores: Vec<PlannedFile>,
+    /// Assets with files to download
+    pub downloads: Vec<PlannedDownload>,
+    /// Assets with missing files that are not attempted because they failed recently
+    pub cooling_down: Vec<String>,
+    /// Why the removals are refused, if they exceed the removal limit
+    pub removal_refused: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedRename {
+    pub asset_id: String,
+    pub from: String,
+    pub to: String,
+    /// When the renamed image was downloaded, carried over to the manifest entry
+    #[serde(skip)]
+    downloaded_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedRemoval {
+    pub asset_id: String,
+    pub file_name: String,
+    pub reason: String,
+    /// Whether the file goes to the trash. Only files no longer in the source do, the
+    /// others can be downloaded again.
+    pub to_trash: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedFile {
+    pub asset_id: String,
+    pub file_name: String,
+}
 
-    // Check for files to remove (files that are no longer in the album)
-    let removed_count = remove_deleted_assets(originals_dir, &current_asset_ids)?;
-    if removed_count > 0 {
-        println!("Removed {} assets that are no longer in the album", removed_count);
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedDownload {
+    pub asset_id: String,
+    /// The image, unless it is already in place
+    pub image: Option<String>,
+    /// The metadata sidecar, unless it is already in place or disabled
+    pub sidecar: Option<String>,
+}
+
+/// Works out what a fetch cycle changes in the originals directory, without touching it
+fn plan_changes<S: PhotoSource, C: FetcherConfig>(
+    source: &S,
+    args: &C,
+    originals_dir: &str,
+    working_set: &WorkingSet,
+    state: &FetcherState,
+    now: chrono::DateTime<chrono::Utc>,
+) -> anyhow::Result<FetchPlan> {
+    // When the working set has not changed since a cycle that put all of its files in place,
+    // there is nothing to remove or download, so the originals directory is left alone
+    let fingerprint = working_set_fingerprint(&working_set.file_names);
+    if state.synced.as_deref() == Some(fingerprint.as_str()) {
+        return Ok(FetchPlan { unchanged: true, ..Default::default() });
     }
 
-    // Download assets
-    let mut downloaded_count = 0;
-    for (i, asset) in assets.iter().enumerate() {
-        if i >= max_images {
-            break;
+    let mut plan = FetchPlan::default();
+    let exists = |file_name: &str| Path::new(originals_dir).join(file_name).exists();
+
+    // An asset whose file name changed but whose content did not, e.g. because it was
+    // renamed in Immich, is renamed rather than downloaded again
+    for asset in &working_set.selected {
+        let file_name = source.file_name(asset);
+        let checksum = source.checksum(asset);
+        let Some(previous) = state.assets.get(&asset.id) else {
+            continue;
+        };
+        if previous.file_name != file_name && checksum.is_some() && previous.checksum.as_deref() == checksum
+            && Path::new(originals_dir).join(&previous.file_name).is_file() && !exists(&file_name) {
+            plan.renames.push(PlannedRename {
+                asset_id: asset.id.clone(),
+                from: previous.file_name.clone(),
+                to: file_name,
+                downloaded_at: previous.downloaded_at,
+            });
         }
+    }
+    let renamed_from: std::collections::HashSet<&str> = plan.renames.iter().map(|rename| rename.from.as_str()).collect();
+    let renamed_to: std::collections::HashSet<&str> = plan.renames.iter().map(|rename| rename.to.as_str()).collect();
 
-        let original_path = format!("{}/{}--_--{}",
-                                  originals_dir,
-                                  asset.id,
-                                  asset.original_file_name);
+    // Files that are no longer in any album or rotated out
+    let stale = find_stale_files(originals_dir, &working_set.file_names)?;
+    for (file_name, asset_id) in stale.files.iter().filter(|(file_name, _)| !renamed_from.contains(file_name.as_str())) {
+        let in_source = working_set.file_names.contains_key(asset_id)
+            || working_set.linked_to.contains_key(asset_id)
+            || working_set.listed_ids.contains(asset_id);
+        let reason = if working_set.file_names.contains_key(asset_id) {
+            "replaced by another variant of the asset".to_string()
+        } else if let Some(head_id) = working_set.linked_to.get(asset_id) {
+            format!("shown through asset {} as part of its Live Photo or stack", head_id)
+        } else if working_set.listed_ids.contains(asset_id) {
+            "rotated out of the selection".to_string()
+        } else {
+            "no longer in the source".to_string()
+        };
+        plan.removals.push(PlannedRemoval {
+            asset_id: asset_id.clone(),
+            file_name: file_name.clone(),
+            reason,
+            to_trash: !in_source,
+        });
+    }
 
-        // Skip if file already exists
-        if Path::new(&original_path).exists() {
-            println!("Asset {} already exists, skipping", asset.id);
+    // A temporarily empty or partial listing, or a wrong album ID, must not wipe the originals.
+    // Assets rotated out of the selection or linked to a listed one are still in the source,
+    // and preferred ones such as memories leave by design, so only the others count towards the limit.
+    if let Some(max_percent) = args.max_removal_percent() {
+        let missing: std::collections::HashSet<&str> = plan.removals
+            .iter()
+            .map(|removal| removal.asset_id.as_str())
+            .filter(|asset_id| !working_set.listed_ids.contains(*asset_id) && !working_set.linked_to.contains_key(*asset_id))
+            .filter(|asset_id| !state.assets.get(*asset_id).is_some_and(|entry| entry.preferred))
+            .collect();
+        if missing.len() * 100 > stale.asset_count * max_percent as usize {
+            plan.removal_refused = Some(format!(
+                "Refusing to remove {} of the {} assets in the originals directory because they are missing from the source, \
+                 which is more than {}%. Check the configured albums, or force the removal if it is intended",
+                missing.len(), stale.asset_count, max_percent));
+        }
+    }
+
+    // Collect the files that are still missing
+    let trash_dir = Path::new(originals_dir).join(TRASH_DIR_NAME);
+    for asset in &working_set.selected {
+        let image_file_name = source.file_name(asset);
+        let mut missing = Vec::new();
+        if args.metadata_sidecars() {
+            missing.push(sidecar_file_name(&image_file_name));
+        }
+        missing.push(image_file_name.clone());
+        missing.retain(|file_name| !exists(file_name) && !renamed_to.contains(file_name.as_str()));
+
+        // Bring back files removed recently instead of downloading them again
+        missing.retain(|file_name| {
+            let in_trash = trash_dir.join(file_name).is_file();
+            if in_trash {
+                plan.restores.push(PlannedFile { asset_id: asset.id.clone(), file_name: file_name.clone() });
+            }
+            !in_trash
+        });
+        if missing.is_empty() {
             continue;
         }
 
-        download_asset(client, args, &asset.id, &original_path).await
-            .with_context(|| format!("Failed to download asset {}", asset.id))?;
+        // Do not hammer assets that kept failing in previous cycles
+        if state.cooling_down(&asset.id, now).is_some() {
+            plan.cooling_down.push(asset.id.clone());
+            continue;
+        }
+
+        let image = missing.iter().find(|file_name| **file_name == image_file_name).cloned();
+        let sidecar = missing.into_iter().find(|file_name| *file_name != image_file_name);
+        plan.downloads.push(PlannedDownload { asset_id: asset.id.clone(), image, sidecar });
+    }
+
+    Ok(plan)
+}
+
+/// Works out what the next fetch cycle would rename, remove, restore and download,
+/// without touching the originals directory
+pub async fn plan_fetch<S: PhotoSource, C: FetcherConfig>(
+    source: &S,
+    args: &C,
+    originals_dir: &str,
+    max_images: usize
+) -> anyhow::Result<FetchPlan> {
+    let list = source.list_assets().await?;
+    let state = FetcherState::load(originals_dir)?;
+    let now = chrono::Utc::now();
+
+    let working_set = choose_working_set(source, args, originals_dir, &list, &state, now, max_images);
+    plan_changes(source, args, originals_dir, &working_set, &state, now)
+}
+
+pub async fn fetch_and_download_images<S: PhotoSource, C: FetcherConfig>(
+    source: &S,
+    args: &C,
+    originals_dir: &str,
+    max_images: usize
+) -> anyhow::Result<FetchSummary> {
+    // Fetch assets from all sources. A failure here aborts the cycle, so an asset is only
+    // ever removed once it is gone from every configured source.
+    let list = source.list_assets().await?;
+
+    let mut state = FetcherState::load(originals_dir)?;
+    let now = chrono::Utc::now();
 
-        println!("Downloaded asset {} to {}", asset.id, original_path);
-        downloaded_count += 1;
+    let emptied = empty_trash(originals_dir, args.trash_retention(), now)?;
+    if emptied > 0 {
+        println!("Deleted {} files that were in the trash for more than {}", emptied, humantime::format_duration(args.trash_retention()));
     }
 
-    if downloaded_count > 0 {
-        println!("Successfully downloaded {} new images", downloaded_count);
+    let working_set = choose_working_set(source, args, originals_dir, &list, &state, now, max_images);
+    let plan = plan_changes(source, args, originals_dir, &working_set, &state, now)?;
+
+    let current_asset_ids: std::collections::HashSet<String> = working_set.selected
+        .iter()
+        .map(|asset| asset.id.clone())
+        .collect();
+    state.mark_shown(&current_asset_ids, now);
+    state.retain_shown(&working_set.listed_ids);
+    state.retain_failed(&current_asset_ids);
+
+    // Keep the manifest of the working set up to date
+    for asset in &working_set.selected {
+        let is_preferred = working_set.preferred_ids.contains(asset.id.as_str());
+        state.record_listed(asset, &source.file_name(asset), source.checksum(asset), is_preferred, now);
+    }
+
+    if plan.unchanged {
+        state.save(originals_dir)?;
+        println!("The {} selected assets are unchanged and already in place", working_set.selected.len());
+        report_disk_usage(originals_dir, args.max_originals_bytes())?;
+        return Ok(FetchSummary { skipped: working_set.selected.len(), ..Default::default() });
+    }
+    // Until this cycle completes, the files in place are not known to match any working set
+    if state.synced.take().is_some() {
+        state.save(originals_dir)?;
+    }
+    for rename in &plan.renames {
+        if rename_original(originals_dir, &rename.from, &rename.to)? {
+            println!("Asset {} was renamed, renamed {} to {}", rename.asset_id, rename.from, rename.to);
+            if let Some(downloaded_at) = rename.downloaded_at {
+                state.record_downloaded(&rename.asset_id, downloaded_at);
+            }
+        }
+    }
+
+    remove_partial_downloads(originals_dir)?;
+    let mut summary = FetchSummary {
+        cooling_down: plan.cooling_down.len(),
+        skipped: working_set.selected.len() - plan.downloads.len() - plan.cooling_down.len(),
+        ..Default::default()
+    };
+    // Refused removals keep their files and manifest entries, the rest of the cycle goes ahead
+    match plan.removal_refused {
+        Some(refused) => {
+            eprintln!("{}", refused);
+            summary.removal_refused = Some(refused);
+        }
+        None => {
+            for removal in &plan.removals {
+                println!("Removing {}: {}", removal.file_name, removal.reason);
+                let trash_retention = if removal.to_trash { args.trash_retention() } else { Duration::ZERO };
+                remove_original(originals_dir, &removal.file_name, trash_retention)?;
+                state.record_removed(&removal.file_name, &removal.asset_id, &removal.reason, now);
+            }
+            state.retain_assets(&current_asset_ids);
+            summary.removed = plan.removals.len();
+        }
+    }
+    if summary.removed > 0 {
+        println!("Removed {} assets that are no longer selected", summary.removed);
+    }
+
+    for restore in &plan.restores {
+        if restore_from_trash(originals_dir, &restore.file_name)? {
+            println!("Restored {} from the trash", restore.file_name);
+        }
+    }
+    // Make room for the downloads by deleting the oldest files from the trash early
+    if let Some(max_bytes) = args.max_originals_bytes() {
+        let deleted = shrink_trash(originals_dir, max_bytes.saturating_sub(working_set.bytes))?;
+        if deleted > 0 {
+            println!("Deleted {} files from the trash to stay within {}", deleted, format_bytes(max_bytes));
+        }
+    }
+    for asset_id in &plan.cooling_down {
+        if let Some(failed) = state.cooling_down(asset_id, now) {
+            println!("Asset {} failed {} times, not retrying before {}", asset_id, failed.failures, failed.retry_after);
+        }
+    }
+
+    // Collect the assets that still need to be downloaded
+    let pending: Vec<PendingAsset> = plan.downloads
+        .iter()
+        .filter_map(|download| {
+            let asset = working_set.selected.iter().find(|asset| asset.id == download.asset_id)?;
+            Some(PendingAsset {
+                asset,
+                image_path: download.image.as_ref().map(|file_name| format!("{}/{}", originals_dir, file_name)),
+                sidecar_path: download.sidecar.as_ref().map(|file_name| format!("{}/{}", originals_dir, file_name)),
+            })
+        })
+        .collect();
+
+    // Download several assets at once. A failing asset is recorded in the summary
+    // instead of aborting the cycle, so it does not block the rest of the albums.
+    let pending_count = pending.len();
+    let mut downloads = futures_util::stream::iter(pending)
+        .map(|pending| async move {
+            let result = fetch_pending_asset(source, args, &pending).await;
+            (pending, result)
+        })
+        .buffer_unordered(args.download_concurrency().max(1));
+
+    // Images of unknown size may turn out larger than estimated, so the downloads stop
+    // once the budget is used up. The next cycle chooses again with the actual sizes.
+    let mut used_bytes = working_set.bytes;
+    let mut processed = 0;
+    let mut over_budget = false;
+    while let Some((pending, result)) = downloads.next().await {
+        let asset = pending.asset;
+        processed += 1;
+        match result {
+            Ok(()) => {
+                if let Some(sidecar_path) = &pending.sidecar_path {
+                    println!("Wrote metadata of asset {} to {}", asset.id, sidecar_path);
+                }
+                if let Some(image_path) = &pending.image_path {
+                    println!("Downloaded asset {} to {}", asset.id, image_path);
+                    summary.downloaded += 1;
+                    state.record_downloaded(&asset.id, chrono::Utc::now());
+
+                    if args.max_originals_bytes().is_some() && source.file_size(asset).is_none() {
+                        let actual = fs::metadata(image_path).map_or(0, |metadata| metadata.len());
+                        used_bytes = (used_bytes + actual).saturating_sub(working_set.unknown_size);
+                    }
+                }
+                state.record_success(&asset.id);
+            }
+            Err(e) => {
+                let error = format!("{:#}", e);
+                eprintln!("Failed to download asset {}: {}", asset.id, error);
+                state.record_failure(&asset.id, &error, args.failure_cooldown(), chrono::Utc::now());
+                summary.failed.push((asset.id.clone(), error));
+            }
+        }
+
+        if let Some(max_bytes) = args.max_originals_bytes() {
+            over_budget = used_bytes > max_bytes;
+            if over_budget && processed < pending_count {
+                println!("The originals exceed {}, leaving {} images for the next cycle", format_bytes(max_bytes), pending_count - processed);
+                break;
+            }
+        }
+    }
+    drop(downloads);
+    // Downloads cut short by the budget leave their temporary files behind
+    if over_budget {
+        remove_partial_downloads(originals_dir)?;
+    }
+
+    // Sizes the source did not report are known now
+    if let Some(max_bytes) = args.max_originals_bytes() {
+        let originals_bytes = directory_size(Path::new(originals_dir))?;
+        let deleted = shrink_trash(originals_dir, max_bytes.saturating_sub(originals_bytes))?;
+        if deleted > 0 {
+            println!("Deleted {} files from the trash to stay within {}", deleted, format_bytes(max_bytes));
+        }
+    }
+
+    if summary.failed.is_empty() && summary.cooling_down == 0 && summary.removal_refused.is_none() && !over_budget {
+        state.synced = Some(working_set_fingerprint(&working_set.file_names));
+    }
+    state.save(originals_dir)?;
+
+    if summary.downloaded > 0 {
+        println!("Successfully downloaded {} new images", summary.downloaded);
     } else {
         println!("No new images to download");
     }
+    if summary.cooling_down > 0 {
+        println!("Skipped {} images that failed recently", summary.cooling_down);
+    }
+    if !summary.failed.is_empty() {
+        println!("Failed to download {} images:", summary.failed.len());
+        for (asset_id, error) in &summary.failed {
+            println!("  {}: {}", asset_id, error);
+        }
+    }
     println!("Originals saved to: {}", originals_dir);
+    report_disk_usage(originals_dir, args.max_originals_bytes())?;
 
-    Ok(())
+    Ok(summary)
+}
+
+/// Re-verifies the already downloaded originals against the checksums reported by the source,
+/// removing corrupt files so that the next fetch cycle downloads them again.
+/// Returns the number of removed files.
+pub async fn verify_existing_originals<S: PhotoSource>(
+    source: &S,
+    originals_dir: &str
+) -> anyhow::Result<usize> {
+    let AssetList { preferred, assets, .. } = source.list_assets().await?;
+
+    // Make the next fetch cycle check every file again, e.g. for originals deleted by hand
+    let mut state = FetcherState::load(originals_dir)?;
+    state.synced = None;
+
+    let mut removed_count = 0;
+    for asset in preferred.iter().chain(&assets) {
+        // Only files with a known checksum can be verified
+        let Some(checksum) = source.checksum(asset) else {
+            continue;
+        };
+
+        let original_path = format!("{}/{}", originals_dir, source.file_name(asset));
+        let path = Path::new(&original_path);
+        if !path.exists() {
+            continue;
+        }
+
+        let expected = decode_checksum(checksum)?;
+        if file_sha1(path)? != expected {
+            println!("Asset {} does not match its checksum, removing it to fetch it again", asset.id);
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove file: {:?}", path))?;
+            state.record_removed(&source.file_name(asset), &asset.id, "did not match its checksum", chrono::Utc::now());
+            removed_count += 1;
+        }
+    }
+
+    state.save(originals_dir)?;
+    Ok(removed_count)
 }
 
-/// Removes files from the originals directory that are no longer in the album
-fn remove_deleted_assets(originals_dir: &str, current_asset_ids: &std::collections::HashSet<String>) -> anyhow::Result<usize> {
+/// Returns true for dot-files, which the fetcher uses for its own bookkeeping
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|f| f.to_str())
+        .is_some_and(|f| f.starts_with('.'))
+}
+
+/// Renames the image of an asset whose file name changed in the source. Returns false
+/// if there is no image to rename.
+fn rename_original(originals_dir: &str, old_file_name: &str, new_file_name: &str) -> anyhow::Result<bool> {
+    let old_path = Path::new(originals_dir).join(old_file_name);
+    let new_path = Path::new(originals_dir).join(new_file_name);
+    if !old_path.is_file() || new_path.exists() {
+        return Ok(false);
+    }
+
+    fs::rename(&old_path, &new_path)
+        .with_context(|| format!("Failed to rename file: {:?}", old_path))?;
+    Ok(true)
+}
+
+/// Name of the hidden directory in the originals directory that removed files are moved to
+pub const TRASH_DIR_NAME: &str = ".trash";
+
+/// The files in the originals directory that are no longer wanted
+#[derive(Default)]
+struct StaleFiles {
+    /// Name and asset ID of each file that is no longer wanted
+    files: Vec<(String, String)>,
+    /// Number of assets with files in the originals directory
+    asset_count: usize,
+}
+
+/// Finds the files in the originals directory that are no longer in any of the albums
+/// or were rotated out of the selection. `current_file_names` maps the ID of each current
+/// asset to its expected file names (the image and its sidecar), so that other variants of
+/// an asset (e.g. the original after switching to previews) are found as well.
+fn find_stale_files(originals_dir: &str, current_file_names: &std::collections::HashMap<String, Vec<String>>) -> anyhow::Result<StaleFiles> {
+    // A dry run may look at an originals directory that does not exist yet
+    if !Path::new(originals_dir).exists() {
+        return Ok(StaleFiles::default());
+    }
+
     let entries = fs::read_dir(originals_dir)
         .context("Failed to read originals directory")?;
 
-    let mut removed_count = 0;
+    let mut files = Vec::new();
+    let mut asset_ids = std::collections::HashSet::new();
 
     for entry in entries {
         let entry = entry?;
         let path = entry.path();
 
-        if !path.is_file() {
+        // Hidden files are not originals
+        if !path.is_file() || is_hidden(&path) {
             continue;
         }
 
@@ -147,19 +1166,149 @@ fn remove_deleted_assets(originals_dir: &str, current_asset_ids: &std::collectio
         if let Some(filename) = path.file_name().and_then(|f| f.to_str()) {
             if let Some(separator_pos) = filename.find("--_--") {
                 let asset_id = &filename[0..separator_pos];
+                asset_ids.insert(asset_id.to_string());
 
-                // If this asset is no longer in the album, remove it
-                if !current_asset_ids.contains(asset_id) {
-                    println!("Removing asset {} as it's no longer in the album", asset_id);
-                    fs::remove_file(&path)
-                        .with_context(|| format!("Failed to remove file: {:?}", path))?;
-                    removed_count += 1;
+                // Keep the variant of the asset that is wanted now, remove any other
+                let wanted = current_file_names
+                    .get(asset_id)
+                    .is_some_and(|expected| expected.iter().any(|name| name == filename));
+                if !wanted {
+                    files.push((filename.to_string(), asset_id.to_string()));
                 }
             }
         }
     }
 
-    Ok(removed_count)
+    Ok(StaleFiles { files, asset_count: asset_ids.len() })
+}
+
+/// Removes the partial downloads left behind by an interrupted cycle. This is safe,
+/// since downloads only start after this cleanup.
+fn remove_partial_downloads(originals_dir: &str) -> anyhow::Result<()> {
+    let entries = fs::read_dir(originals_dir)
+        .context("Failed to read originals directory")?;
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && is_hidden(&path) && path.extension().is_some_and(|ext| ext == "part") {
+            println!("Removing leftover partial download {:?}", path);
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove file: {:?}", path))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves a file of the originals directory to the trash, or removes it right away
+/// if the trash is disabled
+fn remove_original(originals_dir: &str, file_name: &str, trash_retention: Duration) -> anyhow::Result<()> {
+    let path = Path::new(originals_dir).join(file_name);
+    if trash_retention.is_zero() {
+        return fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove file: {:?}", path));
+    }
+
+    let trash_dir = Path::new(originals_dir).join(TRASH_DIR_NAME);
+    fs::create_dir_all(&trash_dir)
+        .with_context(|| format!("Failed to create trash directory: {:?}", trash_dir))?;
+    let trash_path = trash_dir.join(file_name);
+    fs::rename(&path, &trash_path)
+        .with_context(|| format!("Failed to move file to the trash: {:?}", path))?;
+
+    // The modification time tells when the file went into the trash
+    fs::File::options()
+        .write(true)
+        .open(&trash_path)
+        .and_then(|file| file.set_modified(std::time::SystemTime::now()))
+        .with_context(|| format!("Failed to update file in the trash: {:?}", trash_path))?;
+    Ok(())
+}
+
+/// Moves a file back from the trash into the originals directory, unless it is already
+/// there. Returns false if the trash does not have it.
+fn restore_from_trash(originals_dir: &str, file_name: &str) -> anyhow::Result<bool> {
+    let trash_path = Path::new(originals_dir).join(TRASH_DIR_NAME).join(file_name);
+    let path = Path::new(originals_dir).join(file_name);
+    if !trash_path.is_file() || path.exists() {
+        return Ok(false);
+    }
+
+    fs::rename(&trash_path, &path)
+        .with_context(|| format!("Failed to restore file from the trash: {:?}", trash_path))?;
+    Ok(true)
+}
+
+/// Deletes the files that went into the trash first until it holds at most `max_bytes`.
+/// Returns the number of deleted files.
+fn shrink_trash(originals_dir: &str, max_bytes: u64) -> anyhow::Result<usize> {
+    let trash_dir = Path::new(originals_dir).join(TRASH_DIR_NAME);
+    if !trash_dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&trash_dir).with_context(|| format!("Failed to read trash directory: {:?}", trash_dir))? {
+        let path = entry?.path();
+        let metadata = fs::metadata(&path)
+            .with_context(|| format!("Failed to read file metadata: {:?}", path))?;
+        if metadata.is_file() {
+            files.push((metadata.modified()?, metadata.len(), path));
+        }
+    }
+    files.sort();
+
+    let mut trash_bytes: u64 = files.iter().map(|(_, size, _)| size).sum();
+    let mut deleted = 0;
+    for (_, size, path) in files {
+        if trash_bytes <= max_bytes {
+            break;
+        }
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove file: {:?}", path))?;
+        trash_bytes -= size;
+        deleted += 1;
+    }
+
+    Ok(deleted)
+}
+
+/// Prints how much space the originals directory uses, including the trash
+pub fn report_disk_usage(originals_dir: &str, max_bytes: Option<u64>) -> anyhow::Result<()> {
+    let trash_bytes = directory_size(&Path::new(originals_dir).join(TRASH_DIR_NAME))?;
+    let total_bytes = directory_size(Path::new(originals_dir))? + trash_bytes;
+    let budget = max_bytes
+        .map(|max_bytes| format!(" of {}", format_bytes(max_bytes)))
+        .unwrap_or_default();
+    println!("Originals use {}{} ({} in the trash)", format_bytes(total_bytes), budget, format_bytes(trash_bytes));
+    Ok(())
+}
+
+/// Deletes the files that have been in the trash for longer than `retention`.
+/// Returns the number of deleted files.
+fn empty_trash(originals_dir: &str, retention: Duration, now: chrono::DateTime<chrono::Utc>) -> anyhow::Result<usize> {
+    let trash_dir = Path::new(originals_dir).join(TRASH_DIR_NAME);
+    if !trash_dir.is_dir() {
+        return Ok(0);
+    }
+
+    let entries = fs::read_dir(&trash_dir)
+        .with_context(|| format!("Failed to read trash directory: {:?}", trash_dir))?;
+    let mut deleted = 0;
+    for entry in entries {
+        let path = entry?.path();
+        let trashed_at = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .with_context(|| format!("Failed to read file metadata: {:?}", path))?;
+        let age = std::time::SystemTime::from(now).duration_since(trashed_at).unwrap_or_default();
+        if path.is_file() && age >= retention {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove file: {:?}", path))?;
+            deleted += 1;
+        }
+    }
+
+    Ok(deleted)
 }
 
 // AI! move everything below this line into a separate file image_transformer_lib.rs
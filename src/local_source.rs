@@ -0,0 +1,135 @@
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::metadata::Sidecar;
+use crate::{file_asset, file_sidecar, has_image_extension, temp_download_path, Asset, AssetList, PhotoSource};
+
+/// Photos from a directory on disk, e.g. a NAS mount, including its subdirectories
+pub struct LocalFolderSource {
+    root: PathBuf,
+    /// Path of each asset found by the last listing, keyed by asset ID
+    paths: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl LocalFolderSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalFolderSource {
+            root: root.into(),
+            paths: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Path of the file an asset was listed from
+    fn source_path(&self, asset: &Asset) -> anyhow::Result<PathBuf> {
+        self.paths
+            .lock()
+            .unwrap()
+            .get(&asset.id)
+            .cloned()
+            .with_context(|| format!("Asset {} was not listed", asset.id))
+    }
+
+    /// Collects the images below `dir`, skipping hidden files and directories. Symbolic links
+    /// are followed, but each directory is only visited once, so a link to a parent directory
+    /// cannot loop forever. `visited` holds the canonical paths of the directories seen so far.
+    fn collect_images(&self, dir: &Path, visited: &mut HashSet<PathBuf>, assets: &mut Vec<(PathBuf, Asset)>) -> anyhow::Result<()> {
+        let canonical = fs::canonicalize(dir)
+            .with_context(|| format!("Failed to resolve photo directory: {:?}", dir))?;
+        if !visited.insert(canonical) {
+            println!("Skipping {:?}, it was already visited through another path", dir);
+            return Ok(());
+        }
+
+        let entries = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read photo directory: {:?}", dir))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            // Dot-files, and the thumbnail directories NAS systems such as Synology add (@eaDir)
+            if name.starts_with('.') || name.starts_with('@') {
+                continue;
+            }
+
+            // A broken link or a file removed meanwhile must not fail the whole listing
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    eprintln!("Skipping {:?}, failed to read its metadata: {}", path, e);
+                    continue;
+                }
+            };
+            if metadata.is_dir() {
+                self.collect_images(&path, visited, assets)?;
+            } else if has_image_extension(name) {
+                let asset = self.asset(&path, name, &metadata)?;
+                assets.push((path, asset));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn asset(&self, path: &Path, name: &str, metadata: &fs::Metadata) -> anyhow::Result<Asset> {
+        let relative_path = path.strip_prefix(&self.root)?.to_string_lossy().to_string();
+        let modified = metadata.modified().ok().map(chrono::DateTime::<chrono::Utc>::from);
+
+        // The size or the modification time changes when the file does
+        let size = metadata.len().to_le_bytes();
+        let modified_nanos = modified.map_or(0, |modified| modified.timestamp_nanos_opt().unwrap_or(0)).to_le_bytes();
+        let identity: [&[u8]; 3] = [relative_path.as_bytes(), &size, &modified_nanos];
+        Ok(file_asset(&identity, name.to_string(), modified, Some(metadata.len())))
+    }
+}
+
+impl PhotoSource for LocalFolderSource {
+    async fn list_assets(&self) -> anyhow::Result<AssetList> {
+        let mut found = Vec::new();
+        self.collect_images(&self.root, &mut HashSet::new(), &mut found)?;
+        // Directory listings come in no particular order
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        println!("Found {} images in {:?}", found.len(), self.root);
+
+        let mut paths = self.paths.lock().unwrap();
+        paths.clear();
+        let assets = found
+            .into_iter()
+            .map(|(path, asset)| {
+                paths.insert(asset.id.clone(), path);
+                asset
+            })
+            .collect();
+
+        Ok(AssetList { assets, ..Default::default() })
+    }
+
+    fn file_name(&self, asset: &Asset) -> String {
+        format!("{}--_--{}", asset.id, asset.original_file_name)
+    }
+
+    fn checksum<'a>(&self, _asset: &'a Asset) -> Option<&'a str> {
+        None
+    }
+
+    async fn download_asset(&self, asset: &Asset, output_path: &str) -> anyhow::Result<()> {
+        let source_path = self.source_path(asset)?;
+        let temp_path = temp_download_path(output_path)?;
+
+        if let Err(e) = tokio::fs::copy(&source_path, &temp_path).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e).with_context(|| format!("Failed to copy {:?}", source_path));
+        }
+        tokio::fs::rename(&temp_path, output_path).await
+            .with_context(|| format!("Failed to move download into place: {}", output_path))?;
+        Ok(())
+    }
+
+    async fn fetch_metadata(&self, asset: &Asset) -> anyhow::Result<Sidecar> {
+        Ok(file_sidecar(asset))
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Asset details as returned by Immich's `/api/assets/{id}` endpoint, limited to what the sidecar needs
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssetInfo {
+    pub id: String,
+    pub original_file_name: String,
+    #[serde(default)]
+    pub file_created_at: Option<DateTime<Utc>>,
+    /// Capture time in the time zone the photo was taken in, without an offset
+    #[serde(default)]
+    pub local_date_time: Option<String>,
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default)]
+    pub exif_info: Option<ExifInfo>,
+    #[serde(default)]
+    pub people: Vec<Person>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExifInfo {
+    pub date_time_original: Option<DateTime<Utc>>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens_model: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Person {
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Tag {
+    /// Full tag path, e.g. "Family/Grandma"
+    pub value: String,
+}
+
+/// Metadata written as a JSON sidecar next to each original, for captions and scheduling
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sidecar {
+    pub id: String,
+    pub original_file_name: String,
+    pub captured_at: Option<DateTime<Utc>>,
+    pub local_date_time: Option<String>,
+    pub description: Option<String>,
+    pub is_favorite: bool,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    /// Names of the recognized people, unnamed people are left out
+    pub people: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+impl From<AssetInfo> for Sidecar {
+    fn from(info: AssetInfo) -> Self {
+        let exif = info.exif_info.unwrap_or_default();
+        Sidecar {
+            id: info.id,
+            original_file_name: info.original_file_name,
+            captured_at: exif.date_time_original.or(info.file_created_at),
+            local_date_time: info.local_date_time,
+            description: exif.description.filter(|d| !d.is_empty()),
+            is_favorite: info.is_favorite,
+            city: exif.city,
+            state: exif.state,
+            country: exif.country,
+            latitude: exif.latitude,
+            longitude: exif.longitude,
+            camera_make: exif.make,
+            camera_model: exif.model,
+            lens_model: exif.lens_model,
+            people: info.people
+                .into_iter()
+                .map(|person| person.name)
+                .filter(|name| !name.is_empty())
+                .collect(),
+            tags: info.tags.into_iter().map(|tag| tag.value).collect(),
+        }
+    }
+}
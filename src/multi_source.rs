@@ -0,0 +1,106 @@
+use anyhow::Context;
+
+use crate::metadata::Sidecar;
+use crate::{Asset, AssetList, PhotoSource};
+
+/// Separates the name of a source from the asset ID within that source
+const NAMESPACE_SEPARATOR: char = '.';
+
+/// Photos from several sources of the same kind, e.g. the Immich servers of different
+/// households, feeding one originals directory. Asset IDs are prefixed with the name of
+/// their source, so that IDs from different sources cannot collide.
+pub struct MultiSource<S: PhotoSource> {
+    sources: Vec<(String, S)>,
+}
+
+impl<S: PhotoSource> MultiSource<S> {
+    /// Combines the sources, each with a unique name made of letters, digits, '-' and '_'
+    pub fn new(sources: Vec<(String, S)>) -> anyhow::Result<Self> {
+        let mut names = std::collections::HashSet::new();
+        for (name, _) in &sources {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                anyhow::bail!("Invalid source name {:?}, use letters, digits, '-' and '_'", name);
+            }
+            if !names.insert(name) {
+                anyhow::bail!("Source name {:?} is used more than once", name);
+            }
+        }
+
+        Ok(MultiSource { sources })
+    }
+
+    /// The name and the source of a namespaced asset, and the asset as that source knows it
+    fn resolve<'a>(&self, asset: &'a Asset) -> anyhow::Result<(&'a str, &S, Asset)> {
+        let (name, id) = asset.id
+            .split_once(NAMESPACE_SEPARATOR)
+            .with_context(|| format!("Asset {} has no source name", asset.id))?;
+        let source = self.sources
+            .iter()
+            .find(|(source_name, _)| source_name == name)
+            .map(|(_, source)| source)
+            .with_context(|| format!("Asset {} is from an unknown source", asset.id))?;
+        Ok((name, source, Asset { id: id.to_string(), ..asset.clone() }))
+    }
+}
+
+/// Prefixes the ID of each asset, and of the assets linked to it, with the name of its source
+fn namespace(name: &str, assets: Vec<Asset>) -> impl Iterator<Item = Asset> + '_ {
+    let namespaced = move |id: &str| format!("{}{}{}", name, NAMESPACE_SEPARATOR, id);
+    assets.into_iter().map(move |mut asset| {
+        asset.id = namespaced(&asset.id);
+        asset.linked_ids = asset.linked_ids.iter().map(|id| namespaced(id)).collect();
+        asset.origin = Some(match asset.origin {
+            Some(origin) => format!("{} on {}", origin, name),
+            None => name.to_string(),
+        });
+        asset
+    })
+}
+
+impl<S: PhotoSource> PhotoSource for MultiSource<S> {
+    /// Lists the assets of all sources. A failing source fails the listing, so that
+    /// its assets are not removed because of an unreachable server.
+    async fn list_assets(&self) -> anyhow::Result<AssetList> {
+        let mut list = AssetList::default();
+        for (name, source) in &self.sources {
+            let source_list = source.list_assets().await
+                .with_context(|| format!("Failed to list the assets of source {}", name))?;
+            list.preferred.extend(namespace(name, source_list.preferred));
+            list.assets.extend(namespace(name, source_list.assets));
+            list.held_back.extend(namespace(name, source_list.held_back));
+        }
+        Ok(list)
+    }
+
+    /// The file name the source chose, prefixed with its name like the asset ID
+    fn file_name(&self, asset: &Asset) -> String {
+        match self.resolve(asset) {
+            Ok((name, source, source_asset)) => format!("{}{}{}", name, NAMESPACE_SEPARATOR, source.file_name(&source_asset)),
+            // Only listed assets are ever named, and those are always namespaced
+            Err(_) => format!("{}--_--{}", asset.id, asset.original_file_name),
+        }
+    }
+
+    fn checksum<'a>(&self, asset: &'a Asset) -> Option<&'a str> {
+        // The checksum does not depend on the ID, so the source can look at the namespaced asset
+        let (_, source, _) = self.resolve(asset).ok()?;
+        source.checksum(asset)
+    }
+
+    fn file_size(&self, asset: &Asset) -> Option<u64> {
+        let (_, source, source_asset) = self.resolve(asset).ok()?;
+        source.file_size(&source_asset)
+    }
+
+    async fn download_asset(&self, asset: &Asset, output_path: &str) -> anyhow::Result<()> {
+        let (_, source, source_asset) = self.resolve(asset)?;
+        source.download_asset(&source_asset, output_path).await
+    }
+
+    async fn fetch_metadata(&self, asset: &Asset) -> anyhow::Result<Sidecar> {
+        let (_, source, source_asset) = self.resolve(asset)?;
+        let mut sidecar = source.fetch_metadata(&source_asset).await?;
+        sidecar.id = asset.id.clone();
+        Ok(sidecar)
+    }
+}
@@ -0,0 +1,136 @@
+use reqwest::{header, RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// How often a request is repeated after the server asked to retry it later
+const MAX_RETRY_AFTER_ATTEMPTS: u32 = 3;
+
+/// Longest wait a Retry-After header is honoured for; a longer one fails the request instead
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Spreads the requests and downloads of a fetcher over time, so that several fetchers do not
+/// overload a small server. Shared by all requests of the fetcher.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    requests: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    /// No request is sent before this time, after the server asked to retry later
+    paused_until: Mutex<Option<Instant>>,
+}
+
+/// Allows `rate` tokens per second on average, with bursts of up to one second's worth
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    /// Tokens available at `updated`, negative while waiters have reserved more than available
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        TokenBucket {
+            rate,
+            state: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    /// Takes `tokens` out of the bucket, waiting until they are available
+    async fn acquire(&self, tokens: f64) {
+        let wait = {
+            let mut state = self.state.lock().await;
+            let now = Instant::now();
+            let (available, updated) = *state;
+            let available = (available + now.duration_since(updated).as_secs_f64() * self.rate).min(self.rate) - tokens;
+            *state = (available, now);
+            // Reserving the tokens right away keeps the waiters in order
+            (available < 0.0).then(|| Duration::from_secs_f64(-available / self.rate))
+        };
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl RateLimiter {
+    /// A limiter allowing at most `requests_per_second` requests and `bytes_per_second`
+    /// downloaded bytes, each without limit if None
+    pub fn new(requests_per_second: Option<f64>, bytes_per_second: Option<u64>) -> Self {
+        RateLimiter {
+            requests: requests_per_second.filter(|rate| *rate > 0.0).map(TokenBucket::new),
+            bytes: bytes_per_second.filter(|rate| *rate > 0).map(|rate| TokenBucket::new(rate as f64)),
+            paused_until: Mutex::new(None),
+        }
+    }
+
+    /// Waits until another request may be sent
+    pub async fn wait_for_request(&self) {
+        let paused_until = *self.paused_until.lock().await;
+        if let Some(paused_until) = paused_until {
+            tokio::time::sleep_until(paused_until).await;
+        }
+        if let Some(requests) = &self.requests {
+            requests.acquire(1.0).await;
+        }
+    }
+
+    /// Waits until `bytes` more bytes may be downloaded
+    pub async fn wait_for_bytes(&self, bytes: usize) {
+        if let Some(limit) = &self.bytes {
+            limit.acquire(bytes as f64).await;
+        }
+    }
+
+    /// Holds back all requests for `delay`
+    async fn pause(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut paused_until = self.paused_until.lock().await;
+        if paused_until.is_none_or(|paused_until| paused_until < until) {
+            *paused_until = Some(until);
+        }
+    }
+}
+
+/// Sends a request within the limits of the limiter, if any. When the server answers 429 or 503
+/// with a Retry-After header, the request is repeated once that time has passed.
+pub async fn send(limiter: Option<&RateLimiter>, request: RequestBuilder) -> reqwest::Result<Response> {
+    let mut attempt = 0;
+    loop {
+        if let Some(limiter) = limiter {
+            limiter.wait_for_request().await;
+        }
+
+        // Requests with a streamed body cannot be repeated
+        let Some(retry) = request.try_clone() else {
+            return request.send().await;
+        };
+        let response = retry.send().await?;
+
+        attempt += 1;
+        let delay = match response.status() {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => retry_after(&response),
+            _ => None,
+        };
+        match delay {
+            Some(delay) if attempt < MAX_RETRY_AFTER_ATTEMPTS && delay <= MAX_RETRY_AFTER => {
+                println!("{} answered {}, retrying in {}", response.url(), response.status(), humantime::format_duration(delay));
+                match limiter {
+                    Some(limiter) => limiter.pause(delay).await,
+                    None => tokio::time::sleep(delay).await,
+                }
+            }
+            _ => return Ok(response),
+        }
+    }
+}
+
+/// Reads the Retry-After header, which holds either a number of seconds or an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or_default())
+}
@@ -0,0 +1,222 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::{header, Client};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::rate_limit;
+use crate::{Asset, HttpStatusError, ImmichConfig};
+
+/// Number of assets requested per page of search results
+const SEARCH_PAGE_SIZE: usize = 250;
+
+/// Number of search results considered unless configured otherwise
+pub const DEFAULT_SEARCH_LIMIT: usize = 1000;
+
+/// A query selecting assets by their metadata instead of by album.
+/// With a text query, Immich's smart (CLIP) search is used, otherwise the metadata search.
+#[derive(Debug, Clone, clap::Args)]
+pub struct SearchFilter {
+    /// Free text to search for, e.g. "beach sunset"
+    #[arg(long = "search-query", env("SEARCH_QUERY"))]
+    pub query: Option<String>,
+
+    /// Only assets showing all of these people; repeat the flag or separate IDs with commas
+    #[arg(long = "search-person-id", env("SEARCH_PERSON_ID"), value_delimiter = ',')]
+    pub person_ids: Vec<String>,
+
+    /// Only assets with all of these tags; repeat the flag or separate IDs with commas
+    #[arg(long = "search-tag-id", env("SEARCH_TAG_ID"), value_delimiter = ',')]
+    pub tag_ids: Vec<String>,
+
+    /// Only assets taken on or after this date (e.g. 2023-01-31)
+    #[arg(long = "search-taken-after")]
+    pub taken_after: Option<NaiveDate>,
+
+    /// Only assets taken before this date (e.g. 2024-01-31)
+    #[arg(long = "search-taken-before")]
+    pub taken_before: Option<NaiveDate>,
+
+    /// Only assets taken within this time before now (e.g. 2years, 6months)
+    #[arg(long = "search-taken-within", value_parser = humantime::parse_duration)]
+    pub taken_within: Option<Duration>,
+
+    /// Only favourites
+    #[arg(long = "search-favorites")]
+    pub favorites: bool,
+
+    /// Only assets taken in this city
+    #[arg(long = "search-city")]
+    pub city: Option<String>,
+
+    /// Maximum number of search results to consider
+    #[arg(long = "search-limit", default_value_t = DEFAULT_SEARCH_LIMIT)]
+    pub limit: usize,
+}
+
+impl Default for SearchFilter {
+    fn default() -> Self {
+        SearchFilter {
+            query: None,
+            person_ids: Vec::new(),
+            tag_ids: Vec::new(),
+            taken_after: None,
+            taken_before: None,
+            taken_within: None,
+            favorites: false,
+            city: None,
+            limit: DEFAULT_SEARCH_LIMIT,
+        }
+    }
+}
+
+impl SearchFilter {
+    /// Tells whether any criterion is set, i.e. whether the search should be used at all
+    pub fn is_configured(&self) -> bool {
+        self.query.is_some()
+            || !self.person_ids.is_empty()
+            || !self.tag_ids.is_empty()
+            || self.taken_after.is_some()
+            || self.taken_before.is_some()
+            || self.taken_within.is_some()
+            || self.favorites
+            || self.city.is_some()
+    }
+
+    /// The earliest capture time, the later one of `taken_after` and `taken_within`
+    fn earliest(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let after = self.taken_after.map(start_of_day);
+        let within = self.taken_within
+            .and_then(|within| chrono::Duration::from_std(within).ok())
+            .and_then(|within| now.checked_sub_signed(within));
+        after.max(within)
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(chrono::NaiveTime::MIN).and_utc()
+}
+
+/// Body of the `/api/search/metadata` and `/api/search/smart` requests
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    person_ids: &'a [String],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tag_ids: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taken_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taken_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_favorite: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    city: Option<&'a str>,
+    /// Archived assets are found with `withArchived` on older servers, and without
+    /// restricting the `visibility` to the timeline on newer ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    with_archived: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visibility: Option<&'static str>,
+    page: u32,
+    size: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    assets: SearchResultPage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResultPage {
+    items: Vec<Asset>,
+    /// Number of the next page as a string, absent on the last page
+    #[serde(default)]
+    next_page: Option<String>,
+}
+
+/// Fetches the assets matching the filter, following the pages of results up to the limit
+pub(crate) async fn search_assets<T: ImmichConfig>(client: &Client, config: &T, filter: &SearchFilter) -> anyhow::Result<Vec<Asset>> {
+    let endpoint = if filter.query.is_some() { "smart" } else { "metadata" };
+    let request = SearchRequest {
+        query: filter.query.as_deref(),
+        person_ids: &filter.person_ids,
+        tag_ids: &filter.tag_ids,
+        taken_after: filter.earliest(Utc::now()),
+        taken_before: filter.taken_before.map(start_of_day),
+        is_favorite: filter.favorites.then_some(true),
+        city: filter.city.as_deref(),
+        with_archived: config.include_archived().then_some(true),
+        visibility: (!config.include_archived()).then_some("timeline"),
+        page: 1,
+        size: SEARCH_PAGE_SIZE,
+    };
+    search_pages(client, config, endpoint, request, filter.limit).await
+}
+
+/// Fetches the assets taken in `[after, before)`, up to `limit` of them
+pub(crate) async fn search_taken_between<T: ImmichConfig>(
+    client: &Client,
+    config: &T,
+    after: DateTime<Utc>,
+    before: DateTime<Utc>,
+    limit: usize,
+) -> anyhow::Result<Vec<Asset>> {
+    let request = SearchRequest {
+        query: None,
+        person_ids: &[],
+        tag_ids: &[],
+        taken_after: Some(after),
+        taken_before: Some(before),
+        is_favorite: None,
+        city: None,
+        with_archived: config.include_archived().then_some(true),
+        visibility: (!config.include_archived()).then_some("timeline"),
+        page: 1,
+        size: SEARCH_PAGE_SIZE,
+    };
+    search_pages(client, config, "metadata", request, limit).await
+}
+
+/// Runs a search, following the pages of results from `request.page` on up to the limit
+async fn search_pages<T: ImmichConfig>(
+    client: &Client,
+    config: &T,
+    endpoint: &str,
+    mut request: SearchRequest<'_>,
+    limit: usize,
+) -> anyhow::Result<Vec<Asset>> {
+    let url = format!("{}/api/search/{}", config.immich_url(), endpoint);
+    // The page size must stay the same across pages, or the pages would overlap
+    request.size = request.size.min(limit.max(1));
+
+    let mut assets = Vec::new();
+    while assets.len() < limit {
+        let search = client.post(&url)
+            .header(header::ACCEPT, "application/json")
+            .header("x-api-key", config.api_key())
+            .json(&request);
+        let response = rate_limit::send(config.rate_limiter(), search).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(anyhow::Error::new(HttpStatusError { status, body })
+                .context("Failed to search assets"));
+        }
+
+        let result: SearchResponse = response.json().await?;
+        assets.extend(result.assets.items);
+
+        match result.assets.next_page.and_then(|next| next.parse().ok()) {
+            Some(next) if next > request.page => request.page = next,
+            _ => break,
+        }
+    }
+
+    assets.truncate(limit);
+    Ok(assets)
+}
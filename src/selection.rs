@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
+use crate::fetcher_state::FetcherState;
+use crate::Asset;
+
+/// How the fetcher chooses which assets of the albums to keep when there are more than `max_images`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SelectionStrategy {
+    /// The first assets in the order Immich returns them
+    #[default]
+    First,
+    /// The most recently captured assets
+    Newest,
+    /// A random sample, re-drawn every rotation period
+    Random,
+    /// The assets that were not shown for the longest time, rotated every rotation period
+    LeastRecentlyShown,
+    /// A random sample re-drawn every rotation period, favouring favourites
+    Weighted,
+}
+
+/// Settings for choosing the working set out of the album assets
+#[derive(Debug, Clone, Copy)]
+pub struct SelectionConfig {
+    pub strategy: SelectionStrategy,
+    /// How long a random or least-recently-shown selection is kept before the next one
+    pub rotation_period: Duration,
+    /// How much more likely a favourite is to be picked by the weighted strategy
+    pub favorite_weight: f64,
+}
+
+/// Number of the rotation period `now` falls into, the same for all cycles within a period
+fn rotation_window(now: DateTime<Utc>, rotation_period: Duration) -> u64 {
+    now.timestamp().max(0) as u64 / rotation_period.as_secs().max(1)
+}
+
+/// Ranks the assets by how much they should be kept, most wanted first.
+/// The caller keeps as many assets from the start of the ranking as it has room for.
+pub(crate) fn rank_assets<'a>(
+    assets: &'a [Asset],
+    config: &SelectionConfig,
+    state: &FetcherState,
+    now: DateTime<Utc>,
+) -> Vec<&'a Asset> {
+    let mut ranked: Vec<&Asset> = assets.iter().collect();
+    let window = rotation_window(now, config.rotation_period);
+
+    match config.strategy {
+        SelectionStrategy::First => {}
+        SelectionStrategy::Newest => {
+            // Assets without a capture date go last
+            ranked.sort_by_key(|asset| std::cmp::Reverse(asset.file_created_at));
+        }
+        SelectionStrategy::Random => {
+            // Seeding with the rotation window keeps the sample stable within the window
+            ranked.sort_by(|a, b| a.id.cmp(&b.id));
+            ranked.shuffle(&mut StdRng::seed_from_u64(window));
+        }
+        SelectionStrategy::Weighted => {
+            // Weighted sampling without replacement (Efraimidis-Spirakis): every asset gets
+            // the key u^(1/weight) for a uniform random u, the highest keys win
+            ranked.sort_by(|a, b| a.id.cmp(&b.id));
+            let mut rng = StdRng::seed_from_u64(window);
+            let mut keyed: Vec<(f64, &Asset)> = ranked
+                .into_iter()
+                .map(|asset| {
+                    let weight = if asset.is_favorite { config.favorite_weight } else { 1.0 };
+                    let key = rng.gen::<f64>().powf(1.0 / weight.max(f64::MIN_POSITIVE));
+                    (key, asset)
+                })
+                .collect();
+            keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+            ranked = keyed.into_iter().map(|(_, asset)| asset).collect();
+        }
+        SelectionStrategy::LeastRecentlyShown => {
+            // Assets shown during the current window stay, the rest are ranked by when they
+            // were last shown, never shown ones first
+            let window_secs = window.saturating_mul(config.rotation_period.as_secs().max(1));
+            let window_start = DateTime::from_timestamp(window_secs as i64, 0).unwrap_or(now);
+            ranked.sort_by_key(|asset| {
+                let last_shown = state.last_shown.get(&asset.id);
+                let shown_in_window = last_shown.is_some_and(|shown| *shown >= window_start);
+                (!shown_in_window, last_shown.copied())
+            });
+        }
+    }
+
+    ranked
+}
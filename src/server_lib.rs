@@ -95,7 +95,7 @@ async fn get_control_panel(data: actix_web::web::Data<AppState>) -> actix_web::R
 
 fn list_image_files(image_dir: &str, order_filename: &str) -> actix_web::Result<Vec<String>> {
     let mut files: Vec<_> = fs::read_dir(image_dir)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?
+        .map_err(actix_web::error::ErrorInternalServerError)?
         .filter_map(|entry| {
             entry.ok().and_then(|e| {
                 let path = e.path();
@@ -103,8 +103,8 @@ fn list_image_files(image_dir: &str, order_filename: &str) -> actix_web::Result<
                     path.file_name()
                         .and_then(|n| n.to_str())
                         .and_then(|s| {
-                            // Exclude metadata files
-                            if s == order_filename || s == "params.json" {
+                            // Exclude metadata files: the order file, params.json and the image sidecars
+                            if s == order_filename || s.ends_with(".json") {
                                 None
                             } else {
                                 Some(s.to_string())
@@ -239,7 +239,7 @@ fn store_parameters(params_file: &str, query_string: &str) -> std::io::Result<()
     for pair in query_string.split('&') {
         if let Some((key, value)) = pair.split_once('=') {
             let decoded_value = urlencoding::decode(value)
-                .unwrap_or_else(|_| std::borrow::Cow::Borrowed(value))
+                .unwrap_or(std::borrow::Cow::Borrowed(value))
                 .to_string();
 
             // Store the parameter with its timestamp
@@ -309,16 +309,13 @@ async fn post_all_images(
 ) -> actix_web::Result<HttpResponse> {
     // Handle reordering if parameters are provided
     if let (Some(target_pos), Some(name)) = (form.move_to, &form.image_name) {
-        match reorder_images(&data.image_order_file, name, target_pos) {
-            Err(err) => {
-                return Ok(HttpResponse::BadRequest()
-                    .content_type("text/html; charset=utf-8")
-                    .body(format!(
-                        "<html><body><h1>Error</h1><p>{}</p><p><a href='/all-images'>Back</a></p></body></html>",
-                        html_escape(&err)
-                    )))
-            }
-            Ok(_) => {}
+        if let Err(err) = reorder_images(&data.image_order_file, name, target_pos) {
+            return Ok(HttpResponse::BadRequest()
+                .content_type("text/html; charset=utf-8")
+                .body(format!(
+                    "<html><body><h1>Error</h1><p>{}</p><p><a href='/all-images'>Back</a></p></body></html>",
+                    html_escape(&err)
+                )))
         }
     }
 
@@ -391,14 +388,14 @@ fn render_all_images_page(data: &actix_web::web::Data<AppState>) -> actix_web::R
             .unwrap_or("Unknown");
 
         // Get file metadata for modification time
-        let modification_time = fs::metadata(&path)
+        let modification_time = fs::metadata(path)
             .and_then(|meta| meta.modified())
             .ok();
 
         let date_str = modification_time
-            .and_then(|time| {
+            .map(|time| {
                 let datetime: DateTime<Local> = time.into();
-                Some(datetime.format("%Y-%m-%d %H:%M:%S").to_string())
+                datetime.format("%Y-%m-%d %H:%M:%S").to_string()
             })
             .unwrap_or_else(|| "Unknown".to_string());
 
@@ -0,0 +1,65 @@
+use actix_web::{post, web, HttpRequest, HttpResponse, http::header};
+use serde_json::json;
+use tokio::sync::Notify;
+
+/// Lets HTTP requests start a fetch cycle right away instead of at the next interval.
+/// Requests that arrive while a cycle is waiting to start or running are merged, so
+/// a burst of them causes at most one more cycle.
+pub struct SyncTrigger {
+    notify: Notify,
+    /// Secret the requests have to present, if any
+    token: Option<String>,
+}
+
+impl SyncTrigger {
+    pub fn new(token: Option<String>) -> Self {
+        SyncTrigger {
+            notify: Notify::new(),
+            token,
+        }
+    }
+
+    /// Asks for a fetch cycle. Returns immediately; the cycle runs as soon as the fetcher is idle.
+    pub fn request(&self) {
+        // Only a single permit is stored, which merges overlapping requests
+        self.notify.notify_one();
+    }
+
+    /// Resolves once a fetch cycle was asked for since the last time it resolved
+    pub async fn requested(&self) {
+        self.notify.notified().await
+    }
+
+    /// Tells whether a request presents the token, either as a bearer token
+    /// or in the `token` query parameter
+    fn is_authorized(&self, req: &HttpRequest) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+
+        let bearer = req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let query = web::Query::<std::collections::HashMap<String, String>>::from_query(req.query_string())
+            .ok()
+            .and_then(|query| query.get("token").cloned());
+        bearer == Some(token.as_str()) || query.as_deref() == Some(token.as_str())
+    }
+}
+
+#[post("/sync")]
+async fn post_sync(trigger: web::Data<SyncTrigger>, req: HttpRequest) -> HttpResponse {
+    if !trigger.is_authorized(&req) {
+        return HttpResponse::Unauthorized().json(json!({ "error": "Missing or wrong token" }));
+    }
+
+    println!("Fetch cycle requested by {}", req.peer_addr().map_or("unknown".to_string(), |addr| addr.to_string()));
+    trigger.request();
+    HttpResponse::Accepted().json(json!({ "status": "scheduled" }))
+}
+
+// Configure the sync endpoint of the fetcher
+pub fn setup_sync(cfg: &mut web::ServiceConfig) {
+    cfg.service(post_sync);
+}
@@ -0,0 +1,274 @@
+use anyhow::Context;
+use reqwest::{header, Client, Method, Url};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::metadata::Sidecar;
+use crate::rate_limit::{self, RateLimiter};
+use crate::{file_asset, file_sidecar, has_image_extension, save_download, Asset, AssetList, HttpStatusError, PhotoSource};
+
+/// Namespace of the WebDAV elements in PROPFIND responses
+const DAV_NAMESPACE: &str = "DAV:";
+
+/// Properties requested for each entry of a folder
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getcontenttype/>
+    <d:getetag/>
+    <d:getlastmodified/>
+    <d:getcontentlength/>
+  </d:prop>
+</d:propfind>"#;
+
+// Trait to abstract the WebDAV configuration
+pub trait WebDavConfig {
+    /// URL of the folder to take the photos from, e.g.
+    /// "https://cloud.example.com/remote.php/dav/files/alice/Photos/"
+    fn webdav_url(&self) -> &str;
+
+    fn webdav_username(&self) -> Option<&str> {
+        None
+    }
+
+    /// Password for basic authentication, for Nextcloud preferably an app password
+    fn webdav_password(&self) -> Option<&str> {
+        None
+    }
+
+    /// Limits the requests and downloads, shared by all sources of the fetcher
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        None
+    }
+}
+
+/// Photos from a folder on a WebDAV server such as Nextcloud, including its subfolders
+pub struct WebDavSource<'a, T: WebDavConfig> {
+    client: &'a Client,
+    config: &'a T,
+    /// URL of each asset found by the last listing, keyed by asset ID
+    urls: Mutex<HashMap<String, Url>>,
+}
+
+/// A file or folder in a PROPFIND response
+#[derive(Debug)]
+struct DavEntry {
+    url: Url,
+    is_collection: bool,
+    content_type: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<chrono::DateTime<chrono::Utc>>,
+    content_length: Option<u64>,
+}
+
+impl<'a, T: WebDavConfig> WebDavSource<'a, T> {
+    pub fn new(client: &'a Client, config: &'a T) -> Self {
+        WebDavSource {
+            client,
+            config,
+            urls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn request(&self, method: Method, url: Url) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, url);
+        match self.config.webdav_username() {
+            Some(username) => request.basic_auth(username, self.config.webdav_password()),
+            None => request,
+        }
+    }
+
+    /// Lists the entries of a folder, without the folder itself
+    async fn list_folder(&self, folder: &Url) -> anyhow::Result<Vec<DavEntry>> {
+        let request = self.request(Method::from_bytes(b"PROPFIND")?, folder.clone())
+            // Servers such as Nextcloud refuse to list a whole tree at once
+            .header("Depth", "1")
+            .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY);
+        let response = rate_limit::send(self.config.rate_limiter(), request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(anyhow::Error::new(HttpStatusError { status, body })
+                .context(format!("Failed to list WebDAV folder {}", folder)));
+        }
+
+        let body = response.text().await?;
+        let entries = parse_multistatus(folder, &body)
+            .with_context(|| format!("Invalid PROPFIND response for {}", folder))?;
+        // Servers encode the paths differently, e.g. Nextcloud percent-encodes parentheses
+        Ok(entries
+            .into_iter()
+            .filter(|entry| decoded_path(&entry.url) != decoded_path(folder))
+            .collect())
+    }
+
+    /// Collects the images below `root`, skipping hidden files and folders
+    async fn collect_images(&self, root: &Url, assets: &mut Vec<(Url, Asset)>) -> anyhow::Result<()> {
+        let mut folders = vec![root.clone()];
+        // Never list a folder twice, however the server spells its path
+        let mut visited = std::collections::HashSet::new();
+        while let Some(folder) = folders.pop() {
+            if !visited.insert(decoded_path(&folder)) {
+                continue;
+            }
+
+            for entry in self.list_folder(&folder).await? {
+                let name = file_name(&entry.url);
+                if name.is_empty() || name.starts_with('.') {
+                    continue;
+                }
+
+                if entry.is_collection {
+                    let mut subfolder = entry.url;
+                    if !subfolder.path().ends_with('/') {
+                        subfolder.set_path(&format!("{}/", subfolder.path()));
+                    }
+                    folders.push(subfolder);
+                } else if is_image(&entry, &name) {
+                    let asset = asset(&entry, name);
+                    assets.push((entry.url, asset));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decoded URL path without a trailing slash, to compare paths however they are encoded
+fn decoded_path(url: &Url) -> String {
+    let path = url.path().trim_end_matches('/');
+    urlencoding::decode(path)
+        .map(|path| path.into_owned())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// Decoded last segment of the URL path
+fn file_name(url: &Url) -> String {
+    let segment = url.path().trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    urlencoding::decode(segment)
+        .map(|name| name.into_owned())
+        .unwrap_or_else(|_| segment.to_string())
+}
+
+fn is_image(entry: &DavEntry, name: &str) -> bool {
+    match &entry.content_type {
+        Some(content_type) if content_type != "application/octet-stream" => content_type.starts_with("image/"),
+        // Without a useful content type, the extension decides
+        _ => has_image_extension(name),
+    }
+}
+
+fn asset(entry: &DavEntry, name: String) -> Asset {
+    // The ETag changes whenever the file does
+    let identity: [&[u8]; 2] = [entry.url.path().as_bytes(), entry.etag.as_deref().unwrap_or_default().as_bytes()];
+    file_asset(&identity, name, entry.last_modified, entry.content_length)
+}
+
+/// Tells whether the node is the WebDAV element `name`
+fn is_dav(node: &roxmltree::Node, name: &str) -> bool {
+    node.tag_name().namespace() == Some(DAV_NAMESPACE) && node.tag_name().name() == name
+}
+
+/// Finds the first WebDAV element `name` below `node`
+fn dav_descendant<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.descendants().find(|descendant| is_dav(descendant, name))
+}
+
+/// Parses a PROPFIND multistatus response. Relative hrefs are resolved against `base`.
+fn parse_multistatus(base: &Url, body: &str) -> anyhow::Result<Vec<DavEntry>> {
+    let document = roxmltree::Document::parse(body)?;
+
+    let mut entries = Vec::new();
+    for response in document.descendants().filter(|node| is_dav(node, "response")) {
+        let Some(href) = dav_descendant(response, "href").and_then(|href| href.text()) else {
+            continue;
+        };
+
+        // Only the properties of a successful propstat are valid
+        let Some(prop) = response
+            .children()
+            .filter(|propstat| is_dav(propstat, "propstat"))
+            .find(|propstat| dav_descendant(*propstat, "status")
+                .and_then(|status| status.text())
+                .is_some_and(|status| status.contains(" 200 ")))
+            .and_then(|propstat| dav_descendant(propstat, "prop"))
+        else {
+            continue;
+        };
+        let text = |name: &str| dav_descendant(prop, name).and_then(|node| node.text()).map(|text| text.trim().to_string());
+
+        entries.push(DavEntry {
+            url: base.join(href.trim())?,
+            is_collection: dav_descendant(prop, "resourcetype").is_some_and(|resource_type| dav_descendant(resource_type, "collection").is_some()),
+            content_type: text("getcontenttype"),
+            etag: text("getetag"),
+            last_modified: text("getlastmodified")
+                .and_then(|modified| chrono::DateTime::parse_from_rfc2822(&modified).ok())
+                .map(|modified| modified.with_timezone(&chrono::Utc)),
+            content_length: text("getcontentlength").and_then(|length| length.parse().ok()),
+        });
+    }
+
+    Ok(entries)
+}
+
+impl<T: WebDavConfig> PhotoSource for WebDavSource<'_, T> {
+    async fn list_assets(&self) -> anyhow::Result<AssetList> {
+        let mut root = Url::parse(self.config.webdav_url())
+            .with_context(|| format!("Invalid WebDAV URL: {}", self.config.webdav_url()))?;
+        if !root.path().ends_with('/') {
+            root.set_path(&format!("{}/", root.path()));
+        }
+
+        let mut found = Vec::new();
+        self.collect_images(&root, &mut found).await?;
+        // Listings come in no particular order
+        found.sort_by(|a, b| a.0.path().cmp(b.0.path()));
+        println!("Found {} images in {}", found.len(), root);
+
+        let mut urls = self.urls.lock().unwrap();
+        urls.clear();
+        let assets = found
+            .into_iter()
+            .map(|(url, asset)| {
+                urls.insert(asset.id.clone(), url);
+                asset
+            })
+            .collect();
+
+        Ok(AssetList { assets, ..Default::default() })
+    }
+
+    fn file_name(&self, asset: &Asset) -> String {
+        format!("{}--_--{}", asset.id, asset.original_file_name)
+    }
+
+    fn checksum<'b>(&self, _asset: &'b Asset) -> Option<&'b str> {
+        None
+    }
+
+    async fn download_asset(&self, asset: &Asset, output_path: &str) -> anyhow::Result<()> {
+        let url = self.urls
+            .lock()
+            .unwrap()
+            .get(&asset.id)
+            .cloned()
+            .with_context(|| format!("Asset {} was not listed", asset.id))?;
+        let response = rate_limit::send(self.config.rate_limiter(), self.request(Method::GET, url)).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(anyhow::Error::new(HttpStatusError { status, body })
+                .context("Failed to download asset"));
+        }
+
+        save_download(response, &asset.id, None, output_path, self.config.rate_limiter()).await
+    }
+
+    async fn fetch_metadata(&self, asset: &Asset) -> anyhow::Result<Sidecar> {
+        Ok(file_sidecar(asset))
+    }
+}
@@ -1,9 +1,8 @@
 use image_server_lib::ImmichConfig;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Child};
 use tempfile::TempDir;
-use reqwest;
 use anyhow::Result;
 
 mod mock_immich_server;
@@ -19,6 +18,13 @@ impl ManagedChild {
         let child = Command::new(command).args(args).spawn()?;
         Ok(ManagedChild { cmdline, child: Some(child) })
     }
+
+    /// Runs the command in `dir`, so the files it keeps in its working directory end up there
+    fn new_in(dir: &Path, command: &str, args: &[&str]) -> Result<Self> {
+        let cmdline = format!("{} {}", command, args.join(" "));
+        let child = Command::new(command).args(args).current_dir(dir).spawn()?;
+        Ok(ManagedChild { cmdline, child: Some(child) })
+    }
 }
 
 impl Drop for ManagedChild {
@@ -35,7 +41,6 @@ impl Drop for ManagedChild {
 
 struct TestEnv {
     // Keep the temporary directory alive for the duration of the test
-    #[allow(dead_code)]
     test_dir: TempDir,
     originals_dir: PathBuf,
     images_dir: PathBuf,
@@ -56,8 +61,8 @@ impl ImmichConfig for TestEnv {
         &self.api_key
     }
 
-    fn album_id(&self) -> &str {
-        &self.album_id
+    fn album_ids(&self) -> &[String] {
+        std::slice::from_ref(&self.album_id)
     }
 }
 
@@ -117,11 +122,11 @@ async fn test_end_to_end_flow() -> Result<()> {
     let originals_dir_str = test_env.originals_dir.to_str().unwrap();
 
     // Make sure the binaries are built to avoid delays when I need to run them
-    let build_fetcher = Command::new("cargo").args(&["build", "--bin", "immich-fetcher"]).output()?;
+    let build_fetcher = Command::new("cargo").args(["build", "--bin", "immich-fetcher"]).output()?;
     assert!(build_fetcher.status.success(), "Failed to build immich-fetcher");
-    let build_transformer = Command::new("cargo").args(&["build", "--bin", "image-transformer"]).output()?;
+    let build_transformer = Command::new("cargo").args(["build", "--bin", "image-transformer"]).output()?;
     assert!(build_transformer.status.success(), "Failed to build image-transformer");
-    let build_server = Command::new("cargo").args(&["build", "--bin", "image-server"]).output()?;
+    let build_server = Command::new("cargo").args(["build", "--bin", "image-server"]).output()?;
     assert!(build_server.status.success(), "Failed to build image-server");
 
     // Run all three services in parallel in the background
@@ -145,14 +150,19 @@ async fn test_end_to_end_flow() -> Result<()> {
         "--conversion-script", "conversion/dummy_convert_image.sh",
     ])?;
 
-    let _image_server = ManagedChild::new("cargo", &[
-        "run",
-        "--bin", "image-server",
-        "--",
+    // The server keeps its image order in the working directory, which is the temporary one
+    let _image_server = ManagedChild::new_in(test_env.test_dir.path(), env!("CARGO_BIN_EXE_image-server"), &[
         "--image-dir", test_env.images_dir.to_str().unwrap(),
     ])?;
-    // Let them all start
-    actix_rt::time::sleep(std::time::Duration::from_secs(1)).await;
+    // Let them all start and process the album, giving up after a while
+    for _ in 0..60 {
+        if fs::read_dir(&test_env.images_dir)?.next().is_some() {
+            break;
+        }
+        actix_rt::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+    // Give the image server a moment to start as well
+    actix_rt::time::sleep(std::time::Duration::from_millis(500)).await;
 
     // Verify the image was downloaded to originals directory
     let downloaded_files = fs::read_dir(&test_env.originals_dir)?
@@ -183,3 +193,48 @@ async fn test_end_to_end_flow() -> Result<()> {
     println!("End-to-end test completed successfully!");
     Ok(())
 }
+
+#[actix_web::test]
+async fn test_fetcher_once_exit_code() -> Result<()> {
+    let mut test_env = setup_test_environment().await?;
+
+    let mock_server_addr = mock_immich_server::start_mock_server(
+        &test_env.album_id,
+        &test_env.asset_id,
+        test_env.test_image_path.to_str().unwrap(),
+        None
+    ).await?;
+    actix_rt::time::sleep(std::time::Duration::from_secs(1)).await;
+    test_env.immich_url = format!("http://{}", mock_server_addr);
+
+    let originals_dir_str = test_env.originals_dir.to_str().unwrap();
+    let run_once = |album_id: &str| {
+        tokio::process::Command::new("cargo")
+            .args([
+                "run",
+                "--bin", "immich-fetcher",
+                "--",
+                "--immich-url", test_env.immich_url.as_str(),
+                "--api-key", test_env.api_key.as_str(),
+                "--album-id", album_id,
+                "--originals-dir", originals_dir_str,
+                "--once",
+            ])
+            .status()
+    };
+
+    let status = run_once(&test_env.album_id).await?;
+    assert!(status.success(), "A successful cycle should exit with 0, got {:?}", status);
+
+    let downloaded_files = fs::read_dir(&test_env.originals_dir)?
+        .map(|res| res.map(|e| e.file_name().to_string_lossy().to_string()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    assert!(downloaded_files.iter().any(|f| f.starts_with(&test_env.asset_id)),
+            "The asset should be downloaded by a single cycle");
+
+    // An unknown album makes the cycle fail
+    let status = run_once("unknown-album").await?;
+    assert_eq!(status.code(), Some(1), "A failed cycle should exit with 1");
+
+    Ok(())
+}
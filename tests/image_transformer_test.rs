@@ -183,3 +183,140 @@ fn test_run_file_watcher() -> Result<()> {
     
     Ok(())
 }
+
+#[test]
+fn test_run_file_watcher_ignores_partial_downloads() -> Result<()> {
+    // Create temporary directories for the test
+    let temp_dir = tempdir()?;
+    let originals_dir = temp_dir.path().join("originals");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(&originals_dir)?;
+    fs::create_dir_all(&output_dir)?;
+
+    // Set up arguments using the dummy conversion script
+    let args = TransformerArgs {
+        originals_dir: originals_dir.to_string_lossy().to_string(),
+        transformed_dir: output_dir.to_string_lossy().to_string(),
+        conversion_script: "conversion/dummy_convert_image.sh".to_string(),
+    };
+
+    let originals_dir_clone = originals_dir.clone();
+    let watcher_handle = std::thread::spawn(move || {
+        run_file_watcher_with_timeout(&args, Some(1500)).unwrap();
+    });
+
+    // Sleep briefly to let the watcher initialize
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    // Write a download in progress the way the fetcher does
+    let partial_path = originals_dir_clone.join(".asset--_--photo.jpg.part");
+    {
+        let mut file = File::create(&partial_path)?;
+        write!(file, "Partial image")?;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    let partial_output = output_dir.join(".asset--_--photo.jpg.png");
+    assert!(!partial_output.exists(), "Partial download should not be converted");
+    assert_eq!(fs::read_dir(&output_dir)?.count(), 0, "Nothing should be converted yet");
+
+    // Complete the download by renaming it into place
+    {
+        let mut file = fs::OpenOptions::new().append(true).open(&partial_path)?;
+        write!(file, " content")?;
+    }
+    let final_path = originals_dir_clone.join("asset--_--photo.jpg");
+    fs::rename(&partial_path, &final_path)?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    let expected_output = output_dir.join("asset--_--photo.png");
+    assert!(expected_output.exists(), "Completed download was not converted");
+    assert_eq!(fs::read_to_string(&expected_output)?, "Partial image content");
+
+    watcher_handle.join().expect("Watcher thread panicked");
+
+    Ok(())
+}
+
+#[test]
+fn test_sidecars_are_copied() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let originals_dir = temp_dir.path().join("originals");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(&originals_dir)?;
+    fs::create_dir_all(&output_dir)?;
+
+    // An image with the metadata sidecar the fetcher writes next to it
+    fs::write(originals_dir.join("asset--_--photo.jpg"), "Test image content")?;
+    fs::write(originals_dir.join("asset--_--photo.json"), r#"{"city":"Lausanne"}"#)?;
+
+    let args = TransformerArgs {
+        originals_dir: originals_dir.to_string_lossy().to_string(),
+        transformed_dir: output_dir.to_string_lossy().to_string(),
+        conversion_script: "conversion/dummy_convert_image.sh".to_string(),
+    };
+
+    process_existing_files(&args)?;
+
+    // The sidecar is copied as it is, next to the converted image
+    assert!(output_dir.join("asset--_--photo.png").exists(), "Image was not converted");
+    assert_eq!(fs::read_to_string(output_dir.join("asset--_--photo.json"))?, r#"{"city":"Lausanne"}"#);
+
+    // Removing the sidecar from the originals removes the copy
+    let watcher_handle = std::thread::spawn(move || {
+        run_file_watcher_with_timeout(&args, Some(1000)).unwrap();
+    });
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    fs::remove_file(originals_dir.join("asset--_--photo.json"))?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    assert!(!output_dir.join("asset--_--photo.json").exists(), "Sidecar copy was not removed");
+    assert!(output_dir.join("asset--_--photo.png").exists(), "Image should be kept");
+
+    watcher_handle.join().expect("Watcher thread panicked");
+
+    Ok(())
+}
+
+#[test]
+fn test_run_file_watcher_handles_files_moved_to_trash() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let originals_dir = temp_dir.path().join("originals");
+    let output_dir = temp_dir.path().join("output");
+    let trash_dir = originals_dir.join(".trash");
+
+    fs::create_dir_all(&trash_dir)?;
+    fs::create_dir_all(&output_dir)?;
+    fs::write(originals_dir.join("asset--_--photo.jpg"), "Test image content")?;
+
+    let args = TransformerArgs {
+        originals_dir: originals_dir.to_string_lossy().to_string(),
+        transformed_dir: output_dir.to_string_lossy().to_string(),
+        conversion_script: "conversion/dummy_convert_image.sh".to_string(),
+    };
+
+    process_existing_files(&args)?;
+    assert!(output_dir.join("asset--_--photo.png").exists(), "Image was not converted");
+
+    let watcher_handle = std::thread::spawn(move || {
+        run_file_watcher_with_timeout(&args, Some(1500)).unwrap();
+    });
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    // The fetcher moves removed originals into its trash
+    fs::rename(originals_dir.join("asset--_--photo.jpg"), trash_dir.join("asset--_--photo.jpg"))?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert!(!output_dir.join("asset--_--photo.png").exists(), "Converted image was not removed");
+
+    // Restoring it from the trash converts it again
+    fs::rename(trash_dir.join("asset--_--photo.jpg"), originals_dir.join("asset--_--photo.jpg"))?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert!(output_dir.join("asset--_--photo.png").exists(), "Restored image was not converted");
+
+    watcher_handle.join().expect("Watcher thread panicked");
+
+    Ok(())
+}
@@ -1,9 +1,31 @@
 use std::fs;
 use serde_json::json;
-use std::path::Path;
-use mockito::Server;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use mockito::{Matcher, Server};
 use tempfile::tempdir;
-use image_server_lib::{ImmichConfig, fetch_and_download_images};
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use image_server_lib::{AssetSize, FetcherConfig, ImmichConfig, ImmichSource, MemoriesConfig, MultiSource, VideoMode, fetch_and_download_images, parse_bytes, plan_fetch, verify_existing_originals};
+use image_server_lib::fetcher_state::{AssetStatus, FetcherState};
+use image_server_lib::search::SearchFilter;
+use image_server_lib::selection::SelectionStrategy;
+
+/// Lists the originals in a directory, leaving out the fetcher's hidden bookkeeping files
+fn list_originals(dir: &str) -> Vec<PathBuf> {
+    let mut originals: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("Failed to read directory")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| !path.file_name().unwrap().to_string_lossy().starts_with('.'))
+        .collect();
+    originals.sort();
+    originals
+}
+
+/// Computes the checksum the way Immich reports it: base64 encoded SHA-1
+fn immich_checksum(data: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(Sha1::digest(data))
+}
 
 #[tokio::test]
 async fn test_download_asset() -> anyhow::Result<()> {
@@ -18,6 +40,7 @@ async fn test_download_asset() -> anyhow::Result<()> {
     // Mock the album endpoint
     let album_id = "test-album-id";
     let asset_id = "test-asset-id";
+    let test_image_content = b"fake image data";
 
     let album_response = json!({
         "id": &album_id,
@@ -29,7 +52,7 @@ async fn test_download_asset() -> anyhow::Result<()> {
             {
                 "id": &asset_id,
                 "type": "IMAGE",
-                "checksum": "abc123",
+                "checksum": immich_checksum(test_image_content),
                 "originalFileName": "test-image.jpg"
             }
         ]
@@ -43,7 +66,6 @@ async fn test_download_asset() -> anyhow::Result<()> {
         .create();
     
     // Setup asset download endpoint mock
-    let test_image_content = b"fake image data";
     let _asset_mock = server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
         .with_status(200)
         .with_header("content-type", "application/octet-stream")
@@ -57,28 +79,26 @@ async fn test_download_asset() -> anyhow::Result<()> {
     let args = TestArgs {
         immich_url: mock_server_url,
         api_key: "test-api-key".to_string(),
-        album_id: album_id.to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
     };
     let max_images = 10;
     let originals_dir = temp_path.clone();
 
     fetch_and_download_images(
-        &client,
+        &ImmichSource::new(&client, &args),
         &args,
         &originals_dir,
         max_images
     ).await.expect("success");
 
-    // Check that the directory contains exactly one file
-    let entries = fs::read_dir(&temp_path)
-        .expect("Failed to read temp directory")
-        .collect::<Result<Vec<_>, _>>()
-        .expect("Failed to collect directory entries");
-    
-    assert_eq!(entries.len(), 1, "Directory should contain exactly one file");
-    
+    // Check that the directory contains exactly one original
+    let entries = list_originals(&temp_path);
+
+    assert_eq!(entries.len(), 1, "Directory should contain exactly one original");
+
     // Get the file path
-    let file_path = entries[0].path();
+    let file_path = entries[0].clone();
     
     // Verify the file was downloaded correctly
     assert!(file_path.exists());
@@ -89,10 +109,29 @@ async fn test_download_asset() -> anyhow::Result<()> {
 }
 
 // Helper struct to mimic the Args struct from the main code
+#[derive(Default)]
 struct TestArgs {
     immich_url: String,
     api_key: String,
-    album_id: String,
+    album_ids: Vec<String>,
+    shared_link_key: Option<String>,
+    shared_link_password: Option<String>,
+    search_filter: Option<SearchFilter>,
+    download_concurrency: usize,
+    download_retries: u32,
+    failure_cooldown: Duration,
+    selection_strategy: SelectionStrategy,
+    rotation_period: Duration,
+    favorite_weight: f64,
+    video_mode: VideoMode,
+    asset_size: AssetSize,
+    include_archived: bool,
+    metadata_sidecars: bool,
+    trash_retention: Duration,
+    max_removal_percent: Option<u32>,
+    max_originals_bytes: Option<u64>,
+    memories: Option<MemoriesConfig>,
+    today: Option<chrono::NaiveDate>,
 }
 
 // Implement the ImmichConfig trait for TestArgs
@@ -100,13 +139,91 @@ impl ImmichConfig for TestArgs {
     fn immich_url(&self) -> &str {
         &self.immich_url
     }
-    
+
     fn api_key(&self) -> &str {
         &self.api_key
     }
 
-    fn album_id(&self) -> &str {
-        &self.album_id
+    fn album_ids(&self) -> &[String] {
+        &self.album_ids
+    }
+
+    fn shared_link_key(&self) -> Option<&str> {
+        self.shared_link_key.as_deref()
+    }
+
+    fn shared_link_password(&self) -> Option<&str> {
+        self.shared_link_password.as_deref()
+    }
+
+    fn search_filter(&self) -> Option<&SearchFilter> {
+        self.search_filter.as_ref()
+    }
+
+    fn video_mode(&self) -> VideoMode {
+        self.video_mode
+    }
+
+    fn asset_size(&self) -> AssetSize {
+        self.asset_size
+    }
+
+    fn include_archived(&self) -> bool {
+        self.include_archived
+    }
+
+    fn memories(&self) -> Option<MemoriesConfig> {
+        self.memories
+    }
+
+    fn today(&self) -> chrono::NaiveDate {
+        self.today.unwrap_or_else(|| chrono::Local::now().date_naive())
+    }
+}
+
+impl FetcherConfig for TestArgs {
+    fn download_concurrency(&self) -> usize {
+        self.download_concurrency
+    }
+
+    fn download_retries(&self) -> u32 {
+        self.download_retries
+    }
+
+    fn retry_base_delay(&self) -> Duration {
+        Duration::from_millis(1)
+    }
+
+    fn failure_cooldown(&self) -> Duration {
+        self.failure_cooldown
+    }
+
+    fn selection_strategy(&self) -> SelectionStrategy {
+        self.selection_strategy
+    }
+
+    fn rotation_period(&self) -> Duration {
+        self.rotation_period
+    }
+
+    fn favorite_weight(&self) -> f64 {
+        self.favorite_weight
+    }
+
+    fn metadata_sidecars(&self) -> bool {
+        self.metadata_sidecars
+    }
+
+    fn trash_retention(&self) -> Duration {
+        self.trash_retention
+    }
+
+    fn max_removal_percent(&self) -> Option<u32> {
+        self.max_removal_percent
+    }
+
+    fn max_originals_bytes(&self) -> Option<u64> {
+        self.max_originals_bytes
     }
 }
 
@@ -124,6 +241,7 @@ async fn test_remove_deleted_assets() -> anyhow::Result<()> {
     let album_id = "test-album-id";
     let asset_id = "test-asset-id";
     let removed_asset_id = "removed-asset-id";
+    let test_image_content = b"fake image data";
 
     // Create a test file that should be removed (simulating a file from a previous fetch)
     let removed_file_path = format!("{}/{}--_--removed-image.jpg", temp_path, removed_asset_id);
@@ -143,7 +261,7 @@ async fn test_remove_deleted_assets() -> anyhow::Result<()> {
             {
                 "id": &asset_id,
                 "type": "IMAGE",
-                "checksum": "abc123",
+                "checksum": immich_checksum(test_image_content),
                 "originalFileName": "test-image.jpg"
             }
             // removed_asset_id is intentionally not included
@@ -158,7 +276,6 @@ async fn test_remove_deleted_assets() -> anyhow::Result<()> {
         .create();
     
     // Setup asset download endpoint mock
-    let test_image_content = b"fake image data";
     let _asset_mock = server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
         .with_status(200)
         .with_header("content-type", "application/octet-stream")
@@ -172,32 +289,30 @@ async fn test_remove_deleted_assets() -> anyhow::Result<()> {
     let args = TestArgs {
         immich_url: mock_server_url,
         api_key: "test-api-key".to_string(),
-        album_id: album_id.to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
     };
     let max_images = 10;
     let originals_dir = temp_path.clone();
 
     // Run fetch_and_download_images which should download the new asset and remove the old one
     fetch_and_download_images(
-        &client,
+        &ImmichSource::new(&client, &args),
         &args,
         &originals_dir,
         max_images
     ).await.expect("Failed to fetch and download images");
 
-    // Check that the directory contains exactly one file (the new one)
-    let entries = fs::read_dir(&temp_path)
-        .expect("Failed to read temp directory")
-        .collect::<Result<Vec<_>, _>>()
-        .expect("Failed to collect directory entries");
-    
-    assert_eq!(entries.len(), 1, "Directory should contain exactly one file");
+    // Check that the directory contains exactly one original (the new one)
+    let entries = list_originals(&temp_path);
+
+    assert_eq!(entries.len(), 1, "Directory should contain exactly one original");
     
     // Verify the removed file no longer exists
     assert!(!Path::new(&removed_file_path).exists(), "Removed asset file should not exist");
     
     // Get the file path of the remaining file
-    let file_path = entries[0].path();
+    let file_path = entries[0].clone();
     
     // Verify the file name contains the correct asset ID
     let file_name = file_path.file_name().unwrap().to_string_lossy();
@@ -209,3 +324,2383 @@ async fn test_remove_deleted_assets() -> anyhow::Result<()> {
     
     Ok(())
 }
+
+#[tokio::test]
+async fn test_multiple_albums() -> anyhow::Result<()> {
+    // Create a temporary directory for test files
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    // Setup mock server
+    let mut server = Server::new_async().await;
+    let mock_server_url = server.url();
+
+    let kitchen_album_id = "kitchen-album-id";
+    let holidays_album_id = "holidays-album-id";
+    let shared_asset_id = "shared-asset-id";
+
+    // An asset that is only in the second album and already downloaded
+    let holidays_file_path = format!("{}/holidays-asset-id--_--beach.jpg", temp_path);
+    fs::write(&holidays_file_path, b"beach data").expect("Failed to write test file");
+
+    // An asset that is no longer in any album
+    let removed_file_path = format!("{}/removed-asset-id--_--old.jpg", temp_path);
+    fs::write(&removed_file_path, b"old image data").expect("Failed to write test file");
+
+    let kitchen_response = json!({
+        "id": kitchen_album_id,
+        "assets": [
+            {
+                "id": "kitchen-asset-id",
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"kitchen data"),
+                "originalFileName": "kitchen.jpg"
+            },
+            {
+                "id": shared_asset_id,
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"shared data"),
+                "originalFileName": "shared.jpg"
+            }
+        ]
+    });
+    let holidays_response = json!({
+        "id": holidays_album_id,
+        "assets": [
+            {
+                "id": shared_asset_id,
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"shared data"),
+                "originalFileName": "shared.jpg"
+            },
+            {
+                "id": "holidays-asset-id",
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"beach data"),
+                "originalFileName": "beach.jpg"
+            }
+        ]
+    });
+
+    let _kitchen_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", kitchen_album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(kitchen_response.to_string())
+        .create();
+    let _holidays_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", holidays_album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(holidays_response.to_string())
+        .create();
+
+    let kitchen_asset_mock = server.mock("GET", "/api/assets/kitchen-asset-id/original")
+        .with_status(200)
+        .with_body(b"kitchen data")
+        .expect(1)
+        .create();
+    // The asset shared by both albums must be downloaded only once
+    let shared_asset_mock = server.mock("GET", format!("/api/assets/{}/original", shared_asset_id).as_str())
+        .with_status(200)
+        .with_body(b"shared data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: mock_server_url,
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![kitchen_album_id.to_string(), holidays_album_id.to_string()],
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10)
+        .await
+        .expect("Failed to fetch and download images");
+
+    kitchen_asset_mock.assert();
+    shared_asset_mock.assert();
+
+    let file_names: Vec<String> = list_originals(&temp_path)
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+
+    assert_eq!(file_names, vec![
+        "holidays-asset-id--_--beach.jpg",
+        "kitchen-asset-id--_--kitchen.jpg",
+        "shared-asset-id--_--shared.jpg",
+    ]);
+    assert!(!Path::new(&removed_file_path).exists(), "Asset gone from every album should be removed");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_checksum_mismatch_is_rejected() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let asset_id = "test-asset-id";
+
+    let album_response = json!({
+        "id": album_id,
+        "assets": [
+            {
+                "id": asset_id,
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"the complete image data"),
+                "originalFileName": "test-image.jpg"
+            }
+        ]
+    });
+    let _album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_response.to_string())
+        .create();
+
+    // Simulate a truncated download
+    let _asset_mock = server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+        .with_status(200)
+        .with_body(b"the complete")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(summary.failed.len(), 1, "A download with a wrong checksum should fail");
+    assert_eq!(summary.failed[0].0, asset_id);
+
+    let entries = list_originals(&temp_path).len();
+    assert_eq!(entries, 0, "A download with a wrong checksum should not be stored");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_existing_originals() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+
+    // One intact and one corrupt original from a previous run
+    let intact_path = format!("{}/intact-asset-id--_--intact.jpg", temp_path);
+    fs::write(&intact_path, b"intact data")?;
+    let corrupt_path = format!("{}/corrupt-asset-id--_--corrupt.jpg", temp_path);
+    fs::write(&corrupt_path, b"corr")?;
+
+    let album_response = json!({
+        "id": album_id,
+        "assets": [
+            {
+                "id": "intact-asset-id",
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"intact data"),
+                "originalFileName": "intact.jpg"
+            },
+            {
+                "id": "corrupt-asset-id",
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"corrupt data"),
+                "originalFileName": "corrupt.jpg"
+            }
+        ]
+    });
+    let _album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_response.to_string())
+        .create();
+    let corrupt_asset_mock = server.mock("GET", "/api/assets/corrupt-asset-id/original")
+        .with_status(200)
+        .with_body(b"corrupt data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+
+    let removed = verify_existing_originals(&ImmichSource::new(&client, &args), &temp_path).await?;
+    assert_eq!(removed, 1, "Only the corrupt original should be removed");
+    assert!(Path::new(&intact_path).exists());
+    assert!(!Path::new(&corrupt_path).exists());
+
+    // The next cycle fetches the corrupt asset again
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    corrupt_asset_mock.assert();
+    assert_eq!(fs::read(&corrupt_path)?, b"corrupt data");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrent_downloads_report_failures() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+
+    // Ten assets where the third one is broken on the server
+    let assets: Vec<_> = (0..10)
+        .map(|i| json!({
+            "id": format!("asset-{}", i),
+            "type": "IMAGE",
+            "checksum": immich_checksum(format!("image {}", i).as_bytes()),
+            "originalFileName": format!("image-{}.jpg", i)
+        }))
+        .collect();
+    let album_response = json!({ "id": album_id, "assets": assets });
+    let _album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_response.to_string())
+        .create();
+
+    let mut asset_mocks = Vec::new();
+    for i in 0..10 {
+        let mock = server.mock("GET", format!("/api/assets/asset-{}/original", i).as_str());
+        let mock = if i == 2 {
+            mock.with_status(500).with_body("Internal error")
+        } else {
+            mock.with_status(200).with_body(format!("image {}", i))
+        };
+        asset_mocks.push(mock.expect(1).create());
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        download_concurrency: 4,
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+
+    for mock in &asset_mocks {
+        mock.assert();
+    }
+    assert_eq!(summary.downloaded, 9, "The broken asset should not stop the others");
+    assert_eq!(summary.failed.len(), 1);
+    assert_eq!(summary.failed[0].0, "asset-2");
+    assert!(summary.failed[0].1.contains("500"), "The failure should carry the error: {}", summary.failed[0].1);
+    assert_eq!(list_originals(&temp_path).len(), 9);
+
+    Ok(())
+}
+
+/// Sets up an album with the given assets and their (intact) content
+fn mock_album(server: &mut mockito::ServerGuard, album_id: &str, assets: &[(&str, &[u8])]) -> mockito::Mock {
+    let assets: Vec<_> = assets
+        .iter()
+        .map(|(asset_id, content)| json!({
+            "id": asset_id,
+            "type": "IMAGE",
+            "checksum": immich_checksum(content),
+            "originalFileName": format!("{}.jpg", asset_id)
+        }))
+        .collect();
+    let album_response = json!({ "id": album_id, "assets": assets });
+    server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_response.to_string())
+        .create()
+}
+
+#[tokio::test]
+async fn test_transient_failure_is_retried() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("flaky-asset", b"flaky data")]);
+
+    // The server fails twice before the download succeeds
+    let failing_mock = server.mock("GET", "/api/assets/flaky-asset/original")
+        .with_status(503)
+        .with_body("Service unavailable")
+        .expect(2)
+        .create();
+    let succeeding_mock = server.mock("GET", "/api/assets/flaky-asset/original")
+        .with_status(200)
+        .with_body(b"flaky data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        download_retries: 3,
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+
+    failing_mock.assert();
+    succeeding_mock.assert();
+    assert_eq!(summary.downloaded, 1);
+    assert!(summary.failed.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_asset_cools_down() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[
+        ("broken-asset", b"broken data"),
+        ("good-asset", b"good data"),
+    ]);
+
+    // A missing asset is not a transient error, so it is not retried within the cycle
+    let broken_mock = server.mock("GET", "/api/assets/broken-asset/original")
+        .with_status(404)
+        .with_body("Not found")
+        .expect(1)
+        .create();
+    let good_mock = server.mock("GET", "/api/assets/good-asset/original")
+        .with_status(200)
+        .with_body(b"good data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        download_retries: 3,
+        failure_cooldown: Duration::from_secs(60 * 60),
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 1);
+    assert_eq!(summary.failed.len(), 1);
+    assert_eq!(summary.failed[0].0, "broken-asset");
+
+    // The next cycle leaves the broken asset alone while it cools down
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(summary.cooling_down, 1);
+    assert!(summary.failed.is_empty());
+
+    broken_mock.assert();
+    good_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_leftover_partial_downloads_are_removed() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    // A download interrupted by a shutdown in a previous run
+    let partial_path = format!("{}/.gone-asset--_--gone.jpg.part", temp_path);
+    fs::write(&partial_path, b"half an ima")?;
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[]);
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert!(!Path::new(&partial_path).exists(), "Leftover partial download should be removed");
+
+    Ok(())
+}
+
+/// Sets up an album of `count` downloadable assets named `asset-0`, `asset-1`, ...
+/// where `extra` adds fields such as the capture date to each asset
+fn mock_rotation_album(
+    server: &mut mockito::ServerGuard,
+    album_id: &str,
+    count: usize,
+    extra: impl Fn(usize) -> serde_json::Value,
+) -> Vec<mockito::Mock> {
+    let assets: Vec<_> = (0..count)
+        .map(|i| {
+            let mut asset = json!({
+                "id": format!("asset-{}", i),
+                "type": "IMAGE",
+                "checksum": immich_checksum(format!("image {}", i).as_bytes()),
+                "originalFileName": format!("image-{}.jpg", i)
+            });
+            if let Some(fields) = extra(i).as_object() {
+                asset.as_object_mut().unwrap().extend(fields.clone());
+            }
+            asset
+        })
+        .collect();
+    let album_response = json!({ "id": album_id, "assets": assets });
+
+    let mut mocks = vec![server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_response.to_string())
+        .create()];
+    for i in 0..count {
+        mocks.push(server.mock("GET", format!("/api/assets/asset-{}/original", i).as_str())
+            .with_status(200)
+            .with_body(format!("image {}", i))
+            .create());
+    }
+    mocks
+}
+
+/// Lists the IDs of the assets in the originals directory
+fn original_asset_ids(dir: &str) -> Vec<String> {
+    let mut ids: Vec<String> = list_originals(dir)
+        .iter()
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            name.split("--_--").next().unwrap().to_string()
+        })
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn test_newest_selection() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    // Capture dates are out of album order, asset-4 has none
+    let dates = ["2020-01-01T00:00:00Z", "2023-06-01T00:00:00Z", "2021-01-01T00:00:00Z", "2024-01-01T00:00:00Z"];
+    let _mocks = mock_rotation_album(&mut server, album_id, 5, |i| match dates.get(i) {
+        Some(date) => json!({ "fileCreatedAt": date }),
+        None => json!({}),
+    });
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        selection_strategy: SelectionStrategy::Newest,
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 2).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1", "asset-3"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_random_selection_is_stable_within_rotation_period() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _mocks = mock_rotation_album(&mut server, album_id, 20, |_| json!({}));
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        selection_strategy: SelectionStrategy::Random,
+        rotation_period: Duration::from_secs(24 * 60 * 60),
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 5).await?;
+    let first_selection = original_asset_ids(&temp_path);
+    assert_eq!(first_selection.len(), 5);
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 5).await?;
+    assert_eq!(original_asset_ids(&temp_path), first_selection, "The sample should not change within the period");
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(summary.removed, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weighted_selection_favours_favourites() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    // Every fifth asset is a favourite
+    let _mocks = mock_rotation_album(&mut server, album_id, 20, |i| json!({ "isFavorite": i % 5 == 0 }));
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        selection_strategy: SelectionStrategy::Weighted,
+        rotation_period: Duration::from_secs(24 * 60 * 60),
+        favorite_weight: 1e9,
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 4).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-0", "asset-10", "asset-15", "asset-5"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_least_recently_shown_selection_rotates() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _mocks = mock_rotation_album(&mut server, album_id, 5, |_| json!({}));
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        selection_strategy: SelectionStrategy::LeastRecentlyShown,
+        rotation_period: Duration::from_secs(1),
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 2).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-0", "asset-1"]);
+
+    // The next rotation period brings in the assets that were never shown
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 2).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-2", "asset-3"]);
+
+    // Then the last one never shown, topped up with the one shown longest ago
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 2).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-0", "asset-4"]);
+
+    Ok(())
+}
+
+/// Sets up an album with an image, a video and an audio file
+fn mock_mixed_album(server: &mut mockito::ServerGuard, album_id: &str) -> mockito::Mock {
+    let album_response = json!({
+        "id": album_id,
+        "assets": [
+            {
+                "id": "image-asset",
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"image data"),
+                "originalFileName": "photo.jpg"
+            },
+            {
+                "id": "video-asset",
+                "type": "VIDEO",
+                "checksum": immich_checksum(b"video data"),
+                "originalFileName": "clip.mov"
+            },
+            {
+                "id": "audio-asset",
+                "type": "AUDIO",
+                "checksum": immich_checksum(b"audio data"),
+                "originalFileName": "voice.m4a"
+            }
+        ]
+    });
+    server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_response.to_string())
+        .create()
+}
+
+#[tokio::test]
+async fn test_videos_are_skipped_by_default() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_mixed_album(&mut server, album_id);
+    let _image_mock = server.mock("GET", "/api/assets/image-asset/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .create();
+    let video_mock = server.mock("GET", mockito::Matcher::Regex("^/api/assets/video-asset/".to_string()))
+        .expect(0)
+        .create();
+    let audio_mock = server.mock("GET", mockito::Matcher::Regex("^/api/assets/audio-asset/".to_string()))
+        .expect(0)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["image-asset"]);
+    video_mock.assert();
+    audio_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_video_previews() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_mixed_album(&mut server, album_id);
+    let _image_mock = server.mock("GET", "/api/assets/image-asset/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .create();
+    let preview_mock = server.mock("GET", "/api/assets/video-asset/thumbnail?size=preview")
+        .with_status(200)
+        .with_header("content-type", "image/jpeg")
+        .with_body(b"video preview data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        video_mode: VideoMode::Preview,
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 2);
+    preview_mock.assert();
+
+    // The preview is stored as a JPEG image named after the video
+    let preview_path = format!("{}/video-asset--_--clip.jpg", temp_path);
+    assert_eq!(fs::read(&preview_path)?, b"video preview data");
+
+    // Previews are not checked against the checksum of the video on startup
+    let removed = verify_existing_originals(&ImmichSource::new(&client, &args), &temp_path).await?;
+    assert_eq!(removed, 0);
+    assert!(Path::new(&preview_path).exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_download_previews_instead_of_originals() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    // The original was downloaded before switching to previews
+    let original_path = format!("{}/heic-asset--_--IMG_0001.HEIC", temp_path);
+    fs::write(&original_path, b"huge original data")?;
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let album_response = json!({
+        "id": album_id,
+        "assets": [
+            {
+                "id": "heic-asset",
+                "type": "IMAGE",
+                "checksum": immich_checksum(b"huge original data"),
+                "originalFileName": "IMG_0001.HEIC"
+            }
+        ]
+    });
+    let _album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_response.to_string())
+        .create();
+    let original_mock = server.mock("GET", "/api/assets/heic-asset/original")
+        .expect(0)
+        .create();
+    let preview_mock = server.mock("GET", "/api/assets/heic-asset/thumbnail?size=preview")
+        .with_status(200)
+        .with_header("content-type", "image/jpeg")
+        .with_body(b"small preview data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        asset_size: AssetSize::Preview,
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    original_mock.assert();
+    preview_mock.assert();
+    assert_eq!(summary.downloaded, 1);
+
+    // Only the preview is kept, so the image is not shown twice
+    let preview_path = format!("{}/heic-asset--_--IMG_0001.jpg", temp_path);
+    assert_eq!(list_originals(&temp_path), vec![PathBuf::from(&preview_path)]);
+    assert_eq!(fs::read(&preview_path)?, b"small preview data");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_download_fullsize() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("raw-asset", b"raw data")]);
+    let fullsize_mock = server.mock("GET", "/api/assets/raw-asset/thumbnail?size=fullsize")
+        .with_status(200)
+        .with_header("content-type", "image/jpeg")
+        .with_body(b"fullsize data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        asset_size: AssetSize::Fullsize,
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    fullsize_mock.assert();
+    assert_eq!(fs::read(format!("{}/raw-asset--_--raw-asset.jpg", temp_path))?, b"fullsize data");
+
+    Ok(())
+}
+
+/// Mocks the asset info endpoint the metadata sidecar is built from
+fn mock_asset_info(server: &mut mockito::ServerGuard, asset_id: &str) -> mockito::Mock {
+    let asset_info = json!({
+        "id": asset_id,
+        "originalFileName": format!("{}.jpg", asset_id),
+        "fileCreatedAt": "2023-07-14T09:30:00.000Z",
+        "localDateTime": "2023-07-14T11:30:00.000Z",
+        "isFavorite": true,
+        "exifInfo": {
+            "dateTimeOriginal": "2023-07-14T09:30:00.000Z",
+            "make": "Canon",
+            "model": "EOS R6",
+            "lensModel": "RF24-105mm F4 L IS USM",
+            "latitude": 46.5197,
+            "longitude": 6.6323,
+            "city": "Lausanne",
+            "state": "Vaud",
+            "country": "Switzerland",
+            "description": "Sunset at the lake"
+        },
+        "people": [
+            { "id": "person-1", "name": "Alice" },
+            { "id": "person-2", "name": "" }
+        ],
+        "tags": [
+            { "id": "tag-1", "name": "Grandma", "value": "Family/Grandma" }
+        ]
+    });
+    server.mock("GET", format!("/api/assets/{}", asset_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(asset_info.to_string())
+        .create()
+}
+
+#[tokio::test]
+async fn test_metadata_sidecars() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("asset-1", b"image data")]);
+    let info_mock = mock_asset_info(&mut server, "asset-1").expect(1);
+    let _asset_mock = server.mock("GET", "/api/assets/asset-1/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        metadata_sidecars: true,
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 1);
+
+    let sidecar_path = Path::new(&temp_path).join("asset-1--_--asset-1.json");
+    assert_eq!(list_originals(&temp_path), vec![
+        Path::new(&temp_path).join("asset-1--_--asset-1.jpg"),
+        sidecar_path.clone(),
+    ]);
+
+    let sidecar: serde_json::Value = serde_json::from_str(&fs::read_to_string(&sidecar_path)?)?;
+    assert_eq!(sidecar["id"], "asset-1");
+    assert_eq!(sidecar["originalFileName"], "asset-1.jpg");
+    assert_eq!(sidecar["capturedAt"], "2023-07-14T09:30:00Z");
+    assert_eq!(sidecar["localDateTime"], "2023-07-14T11:30:00.000Z");
+    assert_eq!(sidecar["description"], "Sunset at the lake");
+    assert_eq!(sidecar["isFavorite"], true);
+    assert_eq!(sidecar["city"], "Lausanne");
+    assert_eq!(sidecar["country"], "Switzerland");
+    assert_eq!(sidecar["latitude"], 46.5197);
+    assert_eq!(sidecar["cameraMake"], "Canon");
+    assert_eq!(sidecar["cameraModel"], "EOS R6");
+    assert_eq!(sidecar["people"], json!(["Alice"]));
+    assert_eq!(sidecar["tags"], json!(["Family/Grandma"]));
+
+    // Existing sidecars are not fetched again
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    info_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metadata_sidecars_are_removed_with_their_asset() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    // A sidecar written for an image that is no longer in the album, and a current image
+    // downloaded before sidecars were enabled
+    fs::write(format!("{}/gone--_--gone.json", temp_path), b"{}")?;
+    fs::write(format!("{}/gone--_--gone.jpg", temp_path), b"old image")?;
+    fs::write(format!("{}/asset-1--_--asset-1.jpg", temp_path), b"image data")?;
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("asset-1", b"image data")]);
+    let _info_mock = mock_asset_info(&mut server, "asset-1");
+    let asset_mock = server.mock("GET", "/api/assets/asset-1/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .expect(0)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        metadata_sidecars: true,
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.removed, 2);
+    assert_eq!(summary.downloaded, 0, "Only the missing sidecar should be fetched");
+    asset_mock.assert();
+
+    assert_eq!(list_originals(&temp_path), vec![
+        Path::new(&temp_path).join("asset-1--_--asset-1.jpg"),
+        Path::new(&temp_path).join("asset-1--_--asset-1.json"),
+    ]);
+
+    // Without sidecars, the leftover sidecar goes away
+    let args = TestArgs { metadata_sidecars: false, ..args };
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.removed, 1);
+    assert_eq!(list_originals(&temp_path), vec![
+        Path::new(&temp_path).join("asset-1--_--asset-1.jpg"),
+    ]);
+
+    Ok(())
+}
+
+/// Builds a page of search results in the format of Immich's search endpoints
+fn search_page(assets: &[(&str, &[u8])], next_page: Option<&str>) -> String {
+    let items: Vec<_> = assets
+        .iter()
+        .map(|(asset_id, content)| json!({
+            "id": asset_id,
+            "type": "IMAGE",
+            "checksum": immich_checksum(content),
+            "originalFileName": format!("{}.jpg", asset_id)
+        }))
+        .collect();
+    json!({
+        "albums": { "total": 0, "count": 0, "items": [], "facets": [] },
+        "assets": { "total": items.len(), "count": items.len(), "items": items, "facets": [], "nextPage": next_page }
+    }).to_string()
+}
+
+#[tokio::test]
+async fn test_metadata_search_follows_pages() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let filter_json = json!({
+        "personIds": ["person-1"],
+        "tagIds": ["tag-1"],
+        "takenAfter": "2023-01-01T00:00:00Z",
+        "takenBefore": "2024-01-01T00:00:00Z",
+        "isFavorite": true,
+        "city": "Lausanne",
+        "visibility": "timeline"
+    });
+    let first_page = server.mock("POST", "/api/search/metadata")
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::PartialJson(filter_json.clone()),
+            mockito::Matcher::PartialJson(json!({ "page": 1 })),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(search_page(&[("asset-1", b"data 1"), ("asset-2", b"data 2")], Some("2")))
+        .expect(1)
+        .create();
+    let second_page = server.mock("POST", "/api/search/metadata")
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::PartialJson(filter_json),
+            mockito::Matcher::PartialJson(json!({ "page": 2 })),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(search_page(&[("asset-3", b"data 3")], None))
+        .expect(1)
+        .create();
+    let mut download_mocks = Vec::new();
+    for (asset_id, content) in [("asset-1", b"data 1"), ("asset-2", b"data 2"), ("asset-3", b"data 3")] {
+        download_mocks.push(server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(content)
+            .create());
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        search_filter: Some(SearchFilter {
+            person_ids: vec!["person-1".to_string()],
+            tag_ids: vec!["tag-1".to_string()],
+            taken_after: Some("2023-01-01".parse()?),
+            taken_before: Some("2024-01-01".parse()?),
+            favorites: true,
+            city: Some("Lausanne".to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    first_page.assert();
+    second_page.assert();
+    assert_eq!(summary.downloaded, 3);
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1", "asset-2", "asset-3"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_smart_search_with_albums() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("album-asset", b"album data"), ("both", b"both data")]);
+    let search_mock = server.mock("POST", "/api/search/smart")
+        .match_body(mockito::Matcher::PartialJson(json!({ "query": "beach sunset", "page": 1, "size": 2 })))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        // More results than the limit, the second page is never requested
+        .with_body(search_page(&[("both", b"both data"), ("beach", b"beach data")], Some("2")))
+        .expect(1)
+        .create();
+    let mut download_mocks = Vec::new();
+    for (asset_id, content) in [("album-asset", &b"album data"[..]), ("both", b"both data"), ("beach", b"beach data")] {
+        download_mocks.push(server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(content)
+            .expect(1)
+            .create());
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        search_filter: Some(SearchFilter {
+            query: Some("beach sunset".to_string()),
+            limit: 2,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    search_mock.assert();
+    for mock in &download_mocks {
+        mock.assert();
+    }
+    assert_eq!(summary.downloaded, 3, "An asset both in the album and the search is downloaded once");
+    assert_eq!(original_asset_ids(&temp_path), vec!["album-asset", "beach", "both"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_source_keeps_originals() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+    fs::write(format!("{}/asset-1--_--asset-1.jpg", temp_path), b"image data")?;
+
+    let server = Server::new_async().await;
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        search_filter: Some(SearchFilter::default()),
+        ..Default::default()
+    };
+
+    let result = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await;
+    assert!(result.is_err(), "A fetcher without albums or search should fail");
+    assert_eq!(list_originals(&temp_path).len(), 1, "No original should be removed");
+
+    Ok(())
+}
+
+/// The date range the fetcher searches for the memories of a day, in the local time zone
+fn memories_search_range(date: &str) -> serde_json::Value {
+    use chrono::TimeZone;
+    let date: chrono::NaiveDate = date.parse().unwrap();
+    let start_of_day = |date: chrono::NaiveDate| chrono::Local
+        .from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    json!({
+        "takenAfter": start_of_day(date),
+        "takenBefore": start_of_day(date.succ_opt().unwrap()),
+    })
+}
+
+/// Mocks the search for the memories of a day, answering with the given assets
+fn mock_memories(server: &mut mockito::ServerGuard, date: &str, assets: &[(&str, &[u8])]) -> mockito::Mock {
+    server.mock("POST", "/api/search/metadata")
+        .match_body(mockito::Matcher::PartialJson(memories_search_range(date)))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(search_page(assets, None))
+        .expect(1)
+        .create()
+}
+
+#[tokio::test]
+async fn test_memories_are_searched_once_a_day() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let last_year_mock = mock_memories(&mut server, "2024-03-10", &[("memory", b"memory data")]);
+    let two_years_ago_mock = mock_memories(&mut server, "2023-03-10", &[]);
+    let _download_mock = server.mock("GET", "/api/assets/memory/original")
+        .with_status(200)
+        .with_body(b"memory data")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        memories: Some(MemoriesConfig { years: 2, min_assets: 1 }),
+        today: Some("2025-03-10".parse()?),
+        ..Default::default()
+    };
+    let source = ImmichSource::new(&client, &args);
+
+    for _ in 0..3 {
+        fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+        assert_eq!(original_asset_ids(&temp_path), vec!["memory"]);
+    }
+
+    last_year_mock.assert();
+    two_years_ago_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_memories_push_out_the_album_within_the_removal_limit() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let album_assets: [(&str, &[u8]); 3] = [("a1", b"a1 data"), ("a2", b"a2 data"), ("a3", b"a3 data")];
+    let _album_mock = mock_album(&mut server, album_id, &album_assets);
+    let _no_memories_mock = mock_memories(&mut server, "2024-03-10", &[]);
+    let _memories_mock = mock_memories(&mut server, "2024-03-11", &[("mem", b"mem data")]);
+    for (asset_id, content) in album_assets.iter().chain(&[("mem", &b"mem data"[..])]) {
+        server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(content)
+            .create();
+    }
+
+    let client = reqwest::Client::new();
+    let mut args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        memories: Some(MemoriesConfig { years: 1, min_assets: 1 }),
+        today: Some("2025-03-10".parse()?),
+        max_removal_percent: Some(50),
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["a1", "a2", "a3"]);
+
+    // The album images are still in Immich, only pushed out by the memories for the day
+    args.today = Some("2025-03-11".parse()?);
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert!(summary.removal_refused.is_none());
+    assert_eq!(summary.removed, 3);
+    assert_eq!(original_asset_ids(&temp_path), vec!["mem"]);
+
+    let state = FetcherState::load(&temp_path)?;
+    assert_eq!(state.removed["a1--_--a1.jpg"].reason, "rotated out of the selection");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_memories_are_swapped_when_the_day_rolls_over() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    // There are enough memories, so the album is listed but not shown
+    let album_mock = mock_album(&mut server, album_id, &[("album-asset", b"album data")]).expect(2);
+    let yesterday_mock = mock_memories(&mut server, "2024-03-10", &[("memory-yesterday", b"yesterday data")]);
+    let today_mock = mock_memories(&mut server, "2024-03-11", &[("memory-today", b"today data")]);
+    // Nothing was taken on these days two years ago
+    let _no_memories_mock = server.mock("POST", "/api/search/metadata")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(search_page(&[], None))
+        .create();
+    let mut download_mocks = Vec::new();
+    for (asset_id, content) in [("memory-yesterday", &b"yesterday data"[..]), ("memory-today", b"today data")] {
+        download_mocks.push(server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(content)
+            .expect(1)
+            .create());
+    }
+
+    let client = reqwest::Client::new();
+    let mut args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        memories: Some(MemoriesConfig { years: 2, min_assets: 1 }),
+        today: Some("2025-03-10".parse()?),
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["memory-yesterday"]);
+
+    // After midnight, the memories of the new day replace the ones of the previous day
+    args.today = Some("2025-03-11".parse()?);
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.removed, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["memory-today"]);
+
+    yesterday_mock.assert();
+    today_mock.assert();
+    album_mock.assert();
+    for mock in &download_mocks {
+        mock.assert();
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_too_few_memories_are_filled_up_from_the_album() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[
+        ("album-1", b"album data 1"),
+        ("memory", b"memory data"),
+        ("album-2", b"album data 2"),
+    ]);
+    let _memories_mock = mock_memories(&mut server, "2024-03-10", &[("memory", b"memory data")]);
+    let mut download_mocks = Vec::new();
+    for (asset_id, content) in [("memory", &b"memory data"[..]), ("album-1", b"album data 1")] {
+        download_mocks.push(server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(content)
+            .expect(1)
+            .create());
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        memories: Some(MemoriesConfig { years: 1, min_assets: 5 }),
+        today: Some("2025-03-10".parse()?),
+        ..Default::default()
+    };
+
+    // The memory comes first, the album fills up the remaining room
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 2).await?;
+    assert_eq!(summary.downloaded, 2);
+    assert_eq!(original_asset_ids(&temp_path), vec!["album-1", "memory"]);
+    for mock in &download_mocks {
+        mock.assert();
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shared_album_link() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let key = "shared-link-key";
+    let token_cookie = "immich_shared_link_token=shared-link-token";
+
+    // The password is exchanged for a token that authenticates the other requests
+    let link_mock = server.mock("GET", "/api/shared-links/me")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("key".to_string(), key.to_string()),
+            Matcher::UrlEncoded("password".to_string(), "secret".to_string()),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({
+            "id": "link-id",
+            "type": "ALBUM",
+            "key": key,
+            "album": { "id": "shared-album", "albumName": "Shared" },
+            "assets": [],
+            "token": "shared-link-token"
+        }).to_string())
+        .expect(1)
+        .create();
+    let album_mock = server.mock("GET", "/api/albums/shared-album")
+        .match_query(Matcher::UrlEncoded("key".to_string(), key.to_string()))
+        .match_header("cookie", token_cookie)
+        .match_header("x-api-key", Matcher::Missing)
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({
+            "id": "shared-album",
+            "assets": [
+                { "id": "shared-asset", "type": "IMAGE", "checksum": immich_checksum(b"shared data"), "originalFileName": "shared.jpg" }
+            ]
+        }).to_string())
+        .expect(1)
+        .create();
+    let download_mock = server.mock("GET", "/api/assets/shared-asset/original")
+        .match_query(Matcher::UrlEncoded("key".to_string(), key.to_string()))
+        .match_header("cookie", token_cookie)
+        .match_header("x-api-key", Matcher::Missing)
+        .with_status(200)
+        .with_body(b"shared data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        shared_link_key: Some(key.to_string()),
+        shared_link_password: Some("secret".to_string()),
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["shared-asset"]);
+
+    link_mock.assert();
+    album_mock.assert();
+    download_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shared_assets_link() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let key = "shared-link-key";
+
+    // A link sharing individual assets lists them itself
+    let _link_mock = server.mock("GET", "/api/shared-links/me")
+        .match_query(Matcher::UrlEncoded("key".to_string(), key.to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({
+            "id": "link-id",
+            "type": "INDIVIDUAL",
+            "key": key,
+            "assets": [
+                { "id": "photo", "type": "IMAGE", "checksum": immich_checksum(b"photo data"), "originalFileName": "photo.jpg" },
+                { "id": "video", "type": "VIDEO", "checksum": immich_checksum(b"video data"), "originalFileName": "video.mp4" }
+            ]
+        }).to_string())
+        .create();
+    let preview_mock = server.mock("GET", "/api/assets/photo/thumbnail")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("size".to_string(), "preview".to_string()),
+            Matcher::UrlEncoded("key".to_string(), key.to_string()),
+        ]))
+        .with_status(200)
+        .with_body(b"photo preview")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        shared_link_key: Some(key.to_string()),
+        asset_size: AssetSize::Preview,
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["photo"]);
+    preview_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unchanged_album_is_not_listed_again() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({
+            "id": album_id,
+            "updatedAt": "2025-01-01T00:00:00.000Z",
+            "assetCount": 1,
+            "assets": [
+                { "id": "asset-1", "type": "IMAGE", "checksum": immich_checksum(b"image data"), "originalFileName": "asset-1.jpg" }
+            ]
+        }).to_string())
+        .expect(1)
+        .create();
+    // Later cycles only check whether the album changed
+    let summary_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=true", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({
+            "id": album_id,
+            "updatedAt": "2025-01-01T00:00:00.000Z",
+            "assetCount": 1,
+            "assets": []
+        }).to_string())
+        .expect(2)
+        .create();
+    let asset_mock = server.mock("GET", "/api/assets/asset-1/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+    let source = ImmichSource::new(&client, &args);
+
+    let summary = fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 1);
+
+    for _ in 0..2 {
+        let summary = fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+        assert_eq!(summary.downloaded, 0);
+        assert_eq!(summary.skipped, 1);
+    }
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1"]);
+
+    album_mock.assert();
+    summary_mock.assert();
+    asset_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_changed_album_is_listed_again() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({
+            "id": album_id,
+            "updatedAt": "2025-01-01T00:00:00.000Z",
+            "assetCount": 1,
+            "assets": [
+                { "id": "asset-1", "type": "IMAGE", "checksum": immich_checksum(b"image data"), "originalFileName": "asset-1.jpg" }
+            ]
+        }).to_string())
+        .expect(2)
+        .create();
+    let summary_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=true", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({
+            "id": album_id,
+            "updatedAt": "2025-01-02T00:00:00.000Z",
+            "assetCount": 1,
+            "assets": []
+        }).to_string())
+        .expect(1)
+        .create();
+    let _asset_mock = server.mock("GET", "/api/assets/asset-1/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+    let source = ImmichSource::new(&client, &args);
+
+    fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+    fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+
+    album_mock.assert();
+    summary_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_asset_without_checksum_is_downloaded() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({
+            "id": album_id,
+            "assets": [
+                { "id": "asset-1", "type": "IMAGE", "originalFileName": "asset-1.jpg" },
+                { "id": "asset-2", "type": "IMAGE", "checksum": "", "originalFileName": "asset-2.jpg" }
+            ]
+        }).to_string())
+        .create();
+    for asset_id in ["asset-1", "asset-2"] {
+        server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(format!("{} data", asset_id))
+            .create();
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 2);
+    assert!(summary.failed.is_empty());
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1", "asset-2"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_album_is_listed_again_when_an_asset_is_archived() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let album = |last_modified: &str, is_archived: bool| json!({
+        "id": album_id,
+        "updatedAt": "2025-01-01T00:00:00.000Z",
+        "assetCount": 1,
+        "lastModifiedAssetTimestamp": last_modified,
+        "assets": [
+            { "id": "asset-1", "type": "IMAGE", "checksum": immich_checksum(b"image data"), "originalFileName": "asset-1.jpg", "isArchived": is_archived }
+        ]
+    }).to_string();
+    let album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album("2025-01-01T00:00:00.000Z", false))
+        .expect(1)
+        .create();
+    let _asset_mock = server.mock("GET", "/api/assets/asset-1/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+    let source = ImmichSource::new(&client, &args);
+
+    fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1"]);
+    album_mock.assert();
+
+    // Archiving the asset leaves the album itself as it was
+    album_mock.remove();
+    let archived_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album("2025-01-02T00:00:00.000Z", true))
+        .expect(1)
+        .create();
+    let _summary_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=true", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album("2025-01-02T00:00:00.000Z", true))
+        .create();
+
+    let summary = fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+    assert_eq!(summary.removed, 1);
+    assert!(original_asset_ids(&temp_path).is_empty());
+    archived_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_originals_deleted_by_hand_are_fetched_after_verification() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("asset-1", b"image data")]);
+    let asset_mock = server.mock("GET", "/api/assets/asset-1/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .expect(2)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    fs::remove_file(format!("{}/asset-1--_--asset-1.jpg", temp_path))?;
+
+    // The working set is unchanged, so the missing file goes unnoticed until the next startup
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 0);
+
+    verify_existing_originals(&ImmichSource::new(&client, &args), &temp_path).await?;
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 1);
+    asset_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_manifest() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+    fs::write(format!("{}/gone--_--gone.jpg", temp_path), b"old image")?;
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[
+        ("broken-asset", b"broken data"),
+        ("good-asset", b"good data"),
+    ]);
+    let _broken_mock = server.mock("GET", "/api/assets/broken-asset/original")
+        .with_status(404)
+        .with_body("Not found")
+        .create();
+    let _good_mock = server.mock("GET", "/api/assets/good-asset/original")
+        .with_status(200)
+        .with_body(b"good data")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        failure_cooldown: Duration::from_secs(60 * 60),
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+
+    let state = FetcherState::load(&temp_path)?;
+    let good = &state.assets["good-asset"];
+    assert_eq!(good.file_name, "good-asset--_--good-asset.jpg");
+    assert_eq!(good.checksum, Some(immich_checksum(b"good data")));
+    assert_eq!(good.origin.as_deref(), Some("album test-album-id"));
+    assert!(good.downloaded_at.is_some());
+    assert_eq!(state.asset_status("good-asset", good, &temp_path), AssetStatus::Synced);
+
+    let broken = &state.assets["broken-asset"];
+    assert!(broken.downloaded_at.is_none());
+    assert_eq!(state.asset_status("broken-asset", broken, &temp_path), AssetStatus::Failed);
+
+    let removed = &state.removed["gone--_--gone.jpg"];
+    assert_eq!(removed.asset_id, "gone");
+    assert_eq!(removed.reason, "no longer in the source");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_renamed_asset_is_not_downloaded_again() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let album_mock = |file_name: &str| json!({
+        "id": album_id,
+        "assets": [
+            { "id": "asset-1", "type": "IMAGE", "checksum": immich_checksum(b"image data"), "originalFileName": file_name }
+        ]
+    }).to_string();
+    let old_album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_mock("old.jpg"))
+        .create();
+    let asset_mock = server.mock("GET", "/api/assets/asset-1/original")
+        .with_status(200)
+        .with_body(b"image data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    let downloaded_at = FetcherState::load(&temp_path)?.assets["asset-1"].downloaded_at;
+
+    // The asset is renamed in Immich, its content stays the same
+    old_album_mock.remove();
+    let _new_album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(album_mock("new.jpg"))
+        .create();
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(summary.removed, 0);
+    assert_eq!(list_originals(&temp_path), vec![Path::new(&temp_path).join("asset-1--_--new.jpg")]);
+    assert_eq!(FetcherState::load(&temp_path)?.assets["asset-1"].downloaded_at, downloaded_at);
+    asset_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_removed_assets_are_restored_from_the_trash() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let _album_mock = mock_album(&mut server, "full-album", &[("asset-1", b"data 1"), ("asset-2", b"data 2")]);
+    let _other_album_mock = mock_album(&mut server, "other-album", &[("asset-1", b"data 1")]);
+    let mut download_mocks = Vec::new();
+    for (asset_id, content) in [("asset-1", &b"data 1"[..]), ("asset-2", b"data 2")] {
+        download_mocks.push(server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(content)
+            .expect(1)
+            .create());
+    }
+
+    let client = reqwest::Client::new();
+    let mut args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec!["full-album".to_string()],
+        trash_retention: Duration::from_secs(60 * 60),
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+
+    // An asset missing from the source goes to the trash
+    args.album_ids = vec!["other-album".to_string()];
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.removed, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1"]);
+    let trashed = Path::new(&temp_path).join(".trash").join("asset-2--_--asset-2.jpg");
+    assert!(trashed.exists());
+
+    // Once it is back in the source, it is restored instead of downloaded again
+    args.album_ids = vec!["full-album".to_string()];
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1", "asset-2"]);
+    assert!(!trashed.exists());
+    for mock in &download_mocks {
+        mock.assert();
+    }
+
+    // Without retention, the trash is emptied
+    args.album_ids = vec!["other-album".to_string()];
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert!(trashed.exists());
+    args.trash_retention = Duration::ZERO;
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert!(!trashed.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_removing_too_many_assets_is_refused() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+    for asset_id in ["asset-1", "asset-2", "asset-3"] {
+        fs::write(format!("{}/{}--_--{}.jpg", temp_path, asset_id, asset_id), b"image data")?;
+    }
+
+    // A partial response, only listing a newly added asset
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("asset-4", b"new data")]);
+    let _asset_mock = server.mock("GET", "/api/assets/asset-4/original")
+        .with_status(200)
+        .with_body(b"new data")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        max_removal_percent: Some(50),
+        ..Default::default()
+    };
+
+    // The removals are skipped, the download still happens
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert!(summary.removal_refused.unwrap().contains("Refusing to remove 3 of the 3 assets"));
+    assert_eq!(summary.removed, 0);
+    assert_eq!(summary.downloaded, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1", "asset-2", "asset-3", "asset-4"]);
+
+    // Forcing the removal lifts the limit
+    let args = TestArgs { max_removal_percent: None, ..args };
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.removed, 3);
+    assert!(summary.removal_refused.is_none());
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-4"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dry_run_leaves_the_originals_untouched() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+    fs::write(format!("{}/asset-1--_--asset-1.jpg", temp_path), b"data 1")?;
+    fs::write(format!("{}/gone--_--gone.jpg", temp_path), b"old image")?;
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("asset-1", b"data 1"), ("asset-2", b"data 2")]);
+    let download_mock = server.mock("GET", "/api/assets/asset-2/original")
+        .with_status(200)
+        .with_body(b"data 2")
+        .expect(0)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        trash_retention: Duration::from_secs(60 * 60),
+        ..Default::default()
+    };
+
+    let plan = plan_fetch(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    let plan = serde_json::to_value(&plan)?;
+    assert_eq!(plan["downloads"], json!([
+        { "assetId": "asset-2", "image": "asset-2--_--asset-2.jpg", "sidecar": null }
+    ]));
+    assert_eq!(plan["removals"], json!([
+        { "assetId": "gone", "fileName": "gone--_--gone.jpg", "reason": "no longer in the source", "toTrash": true }
+    ]));
+    assert_eq!(plan["unchanged"], json!(false));
+
+    // Nothing was downloaded, removed or recorded
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1", "gone"]);
+    assert!(!Path::new(&temp_path).join(".fetcher-state.json").exists());
+    assert!(!Path::new(&temp_path).join(".trash").exists());
+    download_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_originals_are_kept_within_the_disk_budget() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    // The panorama comes first but does not fit next to the others
+    let contents: [(&str, &[u8]); 3] = [("panorama", &[b'p'; 100]), ("photo-1", b"ten bytes!"), ("photo-2", b"ten bytes?")];
+    let assets: Vec<_> = contents
+        .iter()
+        .map(|(asset_id, content)| json!({
+            "id": asset_id,
+            "type": "IMAGE",
+            "checksum": immich_checksum(content),
+            "originalFileName": format!("{}.jpg", asset_id),
+            "exifInfo": { "fileSizeInByte": content.len() }
+        }))
+        .collect();
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "id": album_id, "assets": assets }).to_string())
+        .create();
+    let mut download_mocks = Vec::new();
+    for (asset_id, content) in contents {
+        download_mocks.push(server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(content)
+            .expect(if asset_id == "panorama" { 0 } else { 1 })
+            .create());
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        max_originals_bytes: Some(50),
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 2);
+    assert_eq!(original_asset_ids(&temp_path), vec!["photo-1", "photo-2"]);
+    for mock in &download_mocks {
+        mock.assert();
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_images_of_unknown_size_stay_within_the_disk_budget() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    // Immich does not report the size of previews
+    let asset_ids = ["photo-1", "photo-2", "photo-3", "photo-4", "photo-5"];
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let album: Vec<(&str, &[u8])> = asset_ids.iter().map(|asset_id| (*asset_id, &b"ten bytes!"[..])).collect();
+    let _album_mock = mock_album(&mut server, album_id, &album);
+    let mut preview_mocks = Vec::new();
+    for asset_id in asset_ids {
+        preview_mocks.push(server.mock("GET", format!("/api/assets/{}/thumbnail?size=preview", asset_id).as_str())
+            .with_status(200)
+            .with_body(b"ten bytes!")
+            .create());
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        asset_size: AssetSize::Preview,
+        max_originals_bytes: Some(25),
+        ..Default::default()
+    };
+    let source = ImmichSource::new(&client, &args);
+
+    // The downloads stop once the budget is exceeded
+    let summary = fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 3);
+
+    // Then the images on disk tell how large the others probably are
+    let summary = fetch_and_download_images(&source, &args, &temp_path, 10).await?;
+    assert_eq!(summary.removed, 1);
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(original_asset_ids(&temp_path), vec!["photo-1", "photo-2"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rotated_out_assets_skip_the_trash() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("asset-1", b"data 1"), ("asset-2", b"data 2")]);
+    for (asset_id, content) in [("asset-1", &b"data 1"[..]), ("asset-2", b"data 2")] {
+        server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(content)
+            .create();
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        trash_retention: Duration::from_secs(60 * 60),
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 2).await?;
+
+    // Still in the album, so it can be downloaded again and is deleted right away
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 1).await?;
+    assert_eq!(summary.removed, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1"]);
+    assert!(!Path::new(&temp_path).join(".trash").join("asset-2--_--asset-2.jpg").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_trash_is_shrunk_to_stay_within_the_disk_budget() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+    let trash_dir = Path::new(&temp_path).join(".trash");
+    fs::create_dir(&trash_dir)?;
+    fs::write(trash_dir.join("old--_--old.jpg"), b"old image!")?;
+    fs::File::options()
+        .write(true)
+        .open(trash_dir.join("old--_--old.jpg"))?
+        .set_modified(std::time::SystemTime::now() - Duration::from_secs(60))?;
+    fs::write(trash_dir.join("recent--_--recent.jpg"), b"new image!")?;
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("asset-1", b"ten bytes!")]);
+    let _download_mock = server.mock("GET", "/api/assets/asset-1/original")
+        .with_status(200)
+        .with_body(b"ten bytes!")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        trash_retention: Duration::from_secs(60 * 60),
+        max_originals_bytes: Some(25),
+        ..Default::default()
+    };
+
+    // Next to the download, the trash only has room for the file that went in last
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["asset-1"]);
+    assert!(!trash_dir.join("old--_--old.jpg").exists());
+    assert!(trash_dir.join("recent--_--recent.jpg").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_bytes() {
+    assert_eq!(parse_bytes("1234"), Ok(1234));
+    assert_eq!(parse_bytes("500MB"), Ok(500_000_000));
+    assert_eq!(parse_bytes("1.5 GB"), Ok(1_500_000_000));
+    assert_eq!(parse_bytes("2GiB"), Ok(2 << 30));
+    assert!(parse_bytes("12 parsecs").is_err());
+}
+
+#[tokio::test]
+async fn test_assets_of_several_servers_are_namespaced() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    // Both servers have an asset with the same ID
+    let mut home_server = Server::new_async().await;
+    let _home_album_mock = mock_album(&mut home_server, "home-album", &[("asset-1", b"home data"), ("asset-2", b"home data 2")]);
+    let mut grandma_server = Server::new_async().await;
+    let _grandma_album_mock = mock_album(&mut grandma_server, "grandma-album", &[("asset-1", b"grandma data")]);
+    let mut download_mocks = Vec::new();
+    for (asset_id, content) in [("asset-1", &b"home data"[..]), ("asset-2", b"home data 2")] {
+        download_mocks.push(home_server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .match_header("x-api-key", "home-key")
+            .with_status(200)
+            .with_body(content)
+            .expect(1)
+            .create());
+    }
+    download_mocks.push(grandma_server.mock("GET", "/api/assets/asset-1/original")
+        .match_header("x-api-key", "grandma-key")
+        .with_status(200)
+        .with_body(b"grandma data")
+        .expect(1)
+        .create());
+
+    let client = reqwest::Client::new();
+    let mut home_args = TestArgs {
+        immich_url: home_server.url(),
+        api_key: "home-key".to_string(),
+        album_ids: vec!["home-album".to_string()],
+        ..Default::default()
+    };
+    let grandma_args = TestArgs {
+        immich_url: grandma_server.url(),
+        api_key: "grandma-key".to_string(),
+        album_ids: vec!["grandma-album".to_string()],
+        ..Default::default()
+    };
+
+    let source = MultiSource::new(vec![
+        ("home".to_string(), ImmichSource::new(&client, &home_args)),
+        ("grandma".to_string(), ImmichSource::new(&client, &grandma_args)),
+    ])?;
+    let summary = fetch_and_download_images(&source, &home_args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 3);
+    assert_eq!(original_asset_ids(&temp_path), vec!["grandma.asset-1", "home.asset-1", "home.asset-2"]);
+    assert_eq!(fs::read(format!("{}/grandma.asset-1--_--asset-1.jpg", temp_path))?, b"grandma data");
+    assert_eq!(fs::read(format!("{}/home.asset-1--_--asset-1.jpg", temp_path))?, b"home data");
+    for mock in &download_mocks {
+        mock.assert();
+    }
+
+    let state = FetcherState::load(&temp_path)?;
+    assert_eq!(state.assets["grandma.asset-1"].origin.as_deref(), Some("album grandma-album on grandma"));
+
+    // An asset gone from one server does not take the same ID of the other one with it
+    let _home_album_mock = mock_album(&mut home_server, "other-home-album", &[("asset-2", b"home data 2")]);
+    home_args.album_ids = vec!["other-home-album".to_string()];
+    let source = MultiSource::new(vec![
+        ("home".to_string(), ImmichSource::new(&client, &home_args)),
+        ("grandma".to_string(), ImmichSource::new(&client, &grandma_args)),
+    ])?;
+    let summary = fetch_and_download_images(&source, &home_args, &temp_path, 10).await?;
+    assert_eq!(summary.removed, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["grandma.asset-1", "home.asset-2"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_source_names_must_be_unique_and_usable_in_file_names() {
+    let client = reqwest::Client::new();
+    let args = TestArgs::default();
+    for names in [vec!["home", "home"], vec!["home", "grandma.2"], vec![""]] {
+        let sources = names
+            .into_iter()
+            .map(|name| (name.to_string(), ImmichSource::new(&client, &args)))
+            .collect();
+        assert!(MultiSource::new(sources).is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_trashed_archived_and_hidden_assets_are_excluded() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+    // Trashed in Immich after it was downloaded
+    fs::write(format!("{}/trashed--_--trashed.jpg", temp_path), b"trashed data")?;
+
+    let assets: Vec<_> = [
+        ("visible", json!({})),
+        ("trashed", json!({ "isTrashed": true })),
+        ("archived", json!({ "isArchived": true })),
+        ("archive-visibility", json!({ "visibility": "archive" })),
+        ("hidden", json!({ "visibility": "hidden" })),
+        ("locked", json!({ "visibility": "locked" })),
+    ]
+    .into_iter()
+    .map(|(asset_id, mut fields)| {
+        let content = format!("{} data", asset_id);
+        fields["id"] = json!(asset_id);
+        fields["type"] = json!("IMAGE");
+        fields["checksum"] = json!(immich_checksum(content.as_bytes()));
+        fields["originalFileName"] = json!(format!("{}.jpg", asset_id));
+        fields
+    })
+    .collect();
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "id": album_id, "assets": assets }).to_string())
+        .create();
+    for asset_id in ["visible", "archived", "archive-visibility", "hidden", "locked"] {
+        server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(format!("{} data", asset_id))
+            .create();
+    }
+
+    let client = reqwest::Client::new();
+    let mut args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["visible"]);
+
+    // Archived assets can be included, the others never are
+    args.include_archived = true;
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["archive-visibility", "archived", "visible"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archived_assets_are_searched_when_included() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let search_mock = server.mock("POST", "/api/search/metadata")
+        .match_body(mockito::Matcher::PartialJson(json!({ "withArchived": true })))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(search_page(&[("archived", b"archived data")], None))
+        .expect(1)
+        .create();
+    let _asset_mock = server.mock("GET", "/api/assets/archived/original")
+        .with_status(200)
+        .with_body(b"archived data")
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        search_filter: Some(SearchFilter { favorites: true, ..Default::default() }),
+        include_archived: true,
+        ..Default::default()
+    };
+
+    fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(original_asset_ids(&temp_path), vec!["archived"]);
+    search_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_live_photos_and_stacks_are_fetched_once() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+    // Downloaded before it was stacked behind another photo
+    fs::write(format!("{}/burst-1--_--burst-1.jpg", temp_path), b"burst-1 data")?;
+
+    let burst = json!({ "id": "burst-stack", "primaryAssetId": "burst-2", "assetCount": 3 });
+    let assets: Vec<_> = [
+        ("live-still", "IMAGE", json!({ "livePhotoVideoId": "live-video" })),
+        ("live-video", "VIDEO", json!({})),
+        ("burst-1", "IMAGE", json!({ "stack": burst })),
+        ("burst-2", "IMAGE", json!({ "stack": burst })),
+        ("burst-3", "IMAGE", json!({ "stack": burst })),
+    ]
+    .into_iter()
+    .map(|(asset_id, asset_type, mut fields)| {
+        let content = format!("{} data", asset_id);
+        fields["id"] = json!(asset_id);
+        fields["type"] = json!(asset_type);
+        fields["checksum"] = json!(immich_checksum(content.as_bytes()));
+        fields["originalFileName"] = json!(format!("{}.jpg", asset_id));
+        fields
+    })
+    .collect();
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = server.mock("GET", format!("/api/albums/{}?withoutAssets=false", album_id).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "id": album_id, "assets": assets }).to_string())
+        .create();
+    for asset_id in ["live-still", "burst-2"] {
+        server.mock("GET", format!("/api/assets/{}/original", asset_id).as_str())
+            .with_status(200)
+            .with_body(format!("{} data", asset_id))
+            .expect(1)
+            .create();
+    }
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        // Even videos shown as their preview do not show the video of a Live Photo
+        video_mode: VideoMode::Preview,
+        // Photos stacked behind another one are still in the album
+        max_removal_percent: Some(0),
+        ..Default::default()
+    };
+
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert_eq!(summary.downloaded, 2);
+    assert_eq!(summary.removed, 1);
+    assert_eq!(original_asset_ids(&temp_path), vec!["burst-2", "live-still"]);
+
+    let state = FetcherState::load(&temp_path)?;
+    assert_eq!(state.assets["live-still"].linked_assets, vec!["live-video"]);
+    assert_eq!(state.assets["burst-2"].linked_assets, vec!["burst-1", "burst-3"]);
+    assert_eq!(state.removed["burst-1--_--burst-1.jpg"].reason, "shown through asset burst-2 as part of its Live Photo or stack");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_after_is_honoured() -> anyhow::Result<()> {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let temp_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut server = Server::new_async().await;
+    let album_id = "test-album-id";
+    let _album_mock = mock_album(&mut server, album_id, &[("busy-asset", b"busy data")]);
+
+    // The server is busy at first and tells when to come back
+    let busy_mock = server.mock("GET", "/api/assets/busy-asset/original")
+        .with_status(429)
+        .with_header("retry-after", "1")
+        .with_body("Too many requests")
+        .expect(1)
+        .create();
+    let succeeding_mock = server.mock("GET", "/api/assets/busy-asset/original")
+        .with_status(200)
+        .with_body(b"busy data")
+        .expect(1)
+        .create();
+
+    let client = reqwest::Client::new();
+    let args = TestArgs {
+        immich_url: server.url(),
+        api_key: "test-api-key".to_string(),
+        album_ids: vec![album_id.to_string()],
+        // Not retried by the backoff, only because of the Retry-After header
+        download_retries: 0,
+        ..Default::default()
+    };
+
+    let start = std::time::Instant::now();
+    let summary = fetch_and_download_images(&ImmichSource::new(&client, &args), &args, &temp_path, 10).await?;
+    assert!(start.elapsed() >= Duration::from_secs(1));
+
+    busy_mock.assert();
+    succeeding_mock.assert();
+    assert_eq!(summary.downloaded, 1);
+    assert!(summary.failed.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use image_server_lib::{FetcherConfig, LocalFolderSource, fetch_and_download_images};
+
+#[derive(Default)]
+struct TestArgs {
+    metadata_sidecars: bool,
+}
+
+impl FetcherConfig for TestArgs {
+    fn metadata_sidecars(&self) -> bool {
+        self.metadata_sidecars
+    }
+}
+
+/// Lists the originals in a directory, leaving out the fetcher's hidden bookkeeping files
+fn list_originals(dir: &Path) -> Vec<PathBuf> {
+    let mut originals: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("Failed to read directory")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| !path.file_name().unwrap().to_string_lossy().starts_with('.'))
+        .collect();
+    originals.sort();
+    originals
+}
+
+/// File name of an original without the asset ID prefix
+fn original_name(path: &Path) -> String {
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    name.split("--_--").nth(1).unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_local_folder_source() -> anyhow::Result<()> {
+    let temp_dir = tempdir()?;
+    let photos_dir = temp_dir.path().join("photos");
+    let originals_dir = temp_dir.path().join("originals");
+    fs::create_dir_all(photos_dir.join("2024/summer"))?;
+    fs::create_dir_all(photos_dir.join("@eaDir"))?;
+    fs::create_dir_all(&originals_dir)?;
+
+    fs::write(photos_dir.join("beach.jpg"), b"beach image")?;
+    fs::write(photos_dir.join("2024/summer/Mountain.PNG"), b"mountain image")?;
+    // Not images, hidden files and NAS thumbnails are left out
+    fs::write(photos_dir.join("notes.txt"), b"not an image")?;
+    fs::write(photos_dir.join(".hidden.jpg"), b"hidden image")?;
+    fs::write(photos_dir.join("@eaDir/SYNOPHOTO_THUMB_M.jpg"), b"thumbnail")?;
+
+    let source = LocalFolderSource::new(&photos_dir);
+    let args = TestArgs { metadata_sidecars: true };
+    let originals = originals_dir.to_str().unwrap();
+
+    let summary = fetch_and_download_images(&source, &args, originals, 10).await?;
+    assert_eq!(summary.downloaded, 2);
+
+    let files = list_originals(&originals_dir);
+    let mut names: Vec<String> = files.iter().map(|path| original_name(path)).collect();
+    names.sort();
+    assert_eq!(names, vec!["Mountain.PNG", "Mountain.json", "beach.jpg", "beach.json"]);
+
+    let beach = files.iter().find(|path| original_name(path) == "beach.jpg").unwrap();
+    assert_eq!(fs::read(beach)?, b"beach image");
+
+    let sidecar = files.iter().find(|path| original_name(path) == "beach.json").unwrap();
+    let sidecar: serde_json::Value = serde_json::from_str(&fs::read_to_string(sidecar)?)?;
+    assert_eq!(sidecar["originalFileName"], "beach.jpg");
+    assert!(sidecar["capturedAt"].is_string(), "The capture date should come from the file");
+
+    // Nothing changed, nothing is copied again
+    let summary = fetch_and_download_images(&source, &args, originals, 10).await?;
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(summary.skipped, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_folder_source_follows_changes() -> anyhow::Result<()> {
+    let temp_dir = tempdir()?;
+    let photos_dir = temp_dir.path().join("photos");
+    let originals_dir = temp_dir.path().join("originals");
+    fs::create_dir_all(&photos_dir)?;
+    fs::create_dir_all(&originals_dir)?;
+
+    fs::write(photos_dir.join("kept.jpg"), b"kept image")?;
+    fs::write(photos_dir.join("deleted.jpg"), b"deleted image")?;
+    fs::write(photos_dir.join("edited.jpg"), b"edited image")?;
+
+    let source = LocalFolderSource::new(&photos_dir);
+    let args = TestArgs::default();
+    let originals = originals_dir.to_str().unwrap();
+
+    fetch_and_download_images(&source, &args, originals, 10).await?;
+    assert_eq!(list_originals(&originals_dir).len(), 3);
+
+    // A photo deleted from the folder goes away, an edited one is fetched again
+    fs::remove_file(photos_dir.join("deleted.jpg"))?;
+    fs::write(photos_dir.join("edited.jpg"), b"edited image, now cropped")?;
+
+    let summary = fetch_and_download_images(&source, &args, originals, 10).await?;
+    assert_eq!(summary.removed, 2);
+    assert_eq!(summary.downloaded, 1);
+
+    let files = list_originals(&originals_dir);
+    let mut names: Vec<String> = files.iter().map(|path| original_name(path)).collect();
+    names.sort();
+    assert_eq!(names, vec!["edited.jpg", "kept.jpg"]);
+
+    let edited = files.iter().find(|path| original_name(path) == "edited.jpg").unwrap();
+    assert_eq!(fs::read(edited)?, b"edited image, now cropped");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_local_folder_source_survives_symlink_loops_and_broken_links() -> anyhow::Result<()> {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = tempdir()?;
+    let photos_dir = temp_dir.path().join("photos");
+    let originals_dir = temp_dir.path().join("originals");
+    fs::create_dir_all(photos_dir.join("2024"))?;
+    fs::create_dir_all(&originals_dir)?;
+
+    fs::write(photos_dir.join("2024/beach.jpg"), b"beach image")?;
+    // A link back to the root, and one to a photo that was deleted
+    symlink(&photos_dir, photos_dir.join("2024/all photos"))?;
+    symlink(photos_dir.join("deleted.jpg"), photos_dir.join("broken.jpg"))?;
+
+    let source = LocalFolderSource::new(&photos_dir);
+    let args = TestArgs::default();
+
+    let summary = fetch_and_download_images(&source, &args, originals_dir.to_str().unwrap(), 10).await?;
+    assert_eq!(summary.downloaded, 1);
+    let names: Vec<String> = list_originals(&originals_dir).iter().map(|path| original_name(path)).collect();
+    assert_eq!(names, vec!["beach.jpg"]);
+
+    Ok(())
+}
@@ -1,11 +1,12 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use base64::Engine;
+use sha1::{Digest, Sha1};
 use std::net::TcpListener;
 use serde_json::json;
 use std::path::Path;
 use tokio::fs;
-use anyhow;
 
 // Configuration for our mock server
 pub struct MockServerConfig {
@@ -22,7 +23,13 @@ struct AppState {
 // Handler for album requests
 async fn album_handler(data: web::Data<AppState>) -> impl Responder {
     println!("Mock server received album request");
-    
+
+    // The fetcher verifies downloads against the checksum, so report the real one
+    let checksum = match fs::read(&data.config.test_image_path).await {
+        Ok(image_data) => base64::engine::general_purpose::STANDARD.encode(Sha1::digest(image_data)),
+        Err(_) => String::new(),
+    };
+
     let album_json = json!({
         "id": data.config.album_id,
         "assets": [
@@ -32,7 +39,7 @@ async fn album_handler(data: web::Data<AppState>) -> impl Responder {
                 "originalFileName": "test_image.jpg",
                 "deviceAssetId": "test_image",
                 "ownerId": "test_user",
-                "checksum": "ABCDEFG",
+                "checksum": checksum,
                 "type": "IMAGE"
             }
         ]
@@ -59,6 +66,30 @@ async fn asset_original_handler(data: web::Data<AppState>) -> impl Responder {
     }
 }
 
+// Handler for asset info requests, which the fetcher turns into a metadata sidecar
+async fn asset_info_handler(data: web::Data<AppState>) -> impl Responder {
+    println!("Mock server received asset info request");
+
+    let asset_json = json!({
+        "id": data.config.asset_id,
+        "originalFileName": "test_image.jpg",
+        "fileCreatedAt": "2024-06-01T12:00:00.000Z",
+        "localDateTime": "2024-06-01T14:00:00.000Z",
+        "isFavorite": false,
+        "exifInfo": {
+            "dateTimeOriginal": "2024-06-01T12:00:00.000Z",
+            "city": "Zurich",
+            "country": "Switzerland"
+        },
+        "people": [],
+        "tags": []
+    });
+
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(asset_json.to_string())
+}
+
 // Default 404 handler
 async fn not_found() -> impl Responder {
     HttpResponse::NotFound().body("Not found")
@@ -102,6 +133,10 @@ pub async fn start_mock_server(
                 &format!("/api/albums/{}", config_clone.album_id), 
                 web::get().to(album_handler)
             )
+            .route(
+                &format!("/api/assets/{}", config_clone.asset_id),
+                web::get().to(asset_info_handler)
+            )
             .route(
                 &format!("/api/assets/{}/original", config_clone.asset_id), 
                 web::get().to(asset_original_handler)
@@ -1,5 +1,4 @@
 use clap::Parser;
-use anyhow;
 
 mod mock_immich_server;
 
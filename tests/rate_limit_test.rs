@@ -0,0 +1,43 @@
+use image_server_lib::rate_limit::RateLimiter;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_requests_are_spread_over_time() {
+    let limiter = RateLimiter::new(Some(5.0), None);
+    let start = Instant::now();
+
+    // A burst of one second's worth goes through right away
+    for _ in 0..5 {
+        limiter.wait_for_request().await;
+    }
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    // The rest only at the allowed rate
+    for _ in 0..5 {
+        limiter.wait_for_request().await;
+    }
+    assert!(start.elapsed() >= Duration::from_millis(900));
+}
+
+#[tokio::test]
+async fn test_downloads_are_limited_in_bandwidth() {
+    let limiter = RateLimiter::new(None, Some(1000));
+    let start = Instant::now();
+
+    for _ in 0..3 {
+        limiter.wait_for_bytes(500).await;
+    }
+    assert!(start.elapsed() >= Duration::from_millis(450));
+}
+
+#[tokio::test]
+async fn test_unlimited_rate_does_not_wait() {
+    let limiter = RateLimiter::new(None, None);
+    let start = Instant::now();
+
+    for _ in 0..1000 {
+        limiter.wait_for_request().await;
+        limiter.wait_for_bytes(1_000_000).await;
+    }
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
@@ -429,6 +429,36 @@ async fn test_all_images_empty_directory() -> std::io::Result<()> {
     Ok(())
 }
 
+#[actix_web::test]
+async fn test_all_images_ignores_sidecars() -> std::io::Result<()> {
+    let temp_dir = tempdir()?;
+    let image_path = temp_dir.path().to_str().unwrap().to_string();
+
+    // Images with the metadata sidecars the transformer copies next to them
+    create_test_images_with_pattern(&image_path, "test", 2)?;
+    fs::write(format!("{}/test1.json", image_path), r#"{"city":"Lausanne"}"#)?;
+    fs::write(format!("{}/test2.json", image_path), r#"{"city":"Geneva"}"#)?;
+
+    let app_state = create_app_state(&image_path);
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(setup_app)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/all-images").to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = test::read_body(resp).await;
+    let content = String::from_utf8_lossy(&body).to_string();
+
+    assert!(content.contains("(out of 2)"), "Sidecars should not be counted as images");
+    assert!(!content.contains("test1.json"), "Sidecars should not be listed");
+
+    Ok(())
+}
+
 #[actix_web::test]
 async fn test_file_endpoint() -> std::io::Result<()> {
     // Create a temporary directory with test images
@@ -637,7 +667,7 @@ async fn test_image_and_all_images_same_order() -> std::io::Result<()> {
         if let Some(end) = all_images_content[idx..].find("</div>") {
             let filename = all_images_content[idx..idx + end].to_string();
             all_images_order.push(filename);
-            idx = idx + end;
+            idx += end;
         }
     }
     
@@ -705,7 +735,7 @@ async fn test_reorder_images_move_to_position() -> std::io::Result<()> {
     // Request to move image2.png to position 0
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("image-name", "image2.png"), ("move-to", "0")])
+        .set_form([("image-name", "image2.png"), ("move-to", "0")])
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
@@ -762,7 +792,7 @@ async fn test_reorder_images_persistence() -> std::io::Result<()> {
     // Reorder: move file3.png to position 0
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("image-name", "file3.png"), ("move-to", "0")])
+        .set_form([("image-name", "file3.png"), ("move-to", "0")])
         .to_request();
     let _ = test::call_service(&app, req).await;
     
@@ -804,14 +834,14 @@ async fn test_reorder_multiple_times() -> std::io::Result<()> {
     // Move img4 to position 0
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("image-name", "img4.png"), ("move-to", "0")])
+        .set_form([("image-name", "img4.png"), ("move-to", "0")])
         .to_request();
     let _ = test::call_service(&app, req).await;
 
     // Move img2 to position 1
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("image-name", "img2.png"), ("move-to", "1")])
+        .set_form([("image-name", "img2.png"), ("move-to", "1")])
         .to_request();
     let _ = test::call_service(&app, req).await;
     
@@ -867,7 +897,7 @@ async fn test_reorder_nonexistent_image_returns_error() -> std::io::Result<()> {
     // Try to move a non-existent image
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("image-name", "nonexistent.png"), ("move-to", "0")])
+        .set_form([("image-name", "nonexistent.png"), ("move-to", "0")])
         .to_request();
     let resp = test::call_service(&app, req).await;
     
@@ -1016,8 +1046,8 @@ async fn test_multiple_new_images_near_end_of_list() -> std::io::Result<()> {
     let new_b_idx = new_b_pos.unwrap();
     
     // Both should be at positions 5 or 6
-    assert!(new_a_idx >= 5 && new_a_idx <= 6, "new_a.png should be at position 5 or 6");
-    assert!(new_b_idx >= 5 && new_b_idx <= 6, "new_b.png should be at position 5 or 6");
+    assert!((5..=6).contains(&new_a_idx), "new_a.png should be at position 5 or 6");
+    assert!((5..=6).contains(&new_b_idx), "new_b.png should be at position 5 or 6");
     assert_ne!(new_a_idx, new_b_idx, "new_a and new_b should be at different positions");
     
     // Verify that the order of the original images is preserved (except the ones we didn't see)
@@ -1127,7 +1157,7 @@ async fn test_set_next_index_parameter() -> std::io::Result<()> {
     // Set next index to 3 via /all-images
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("next-index", "3")])
+        .set_form([("next-index", "3")])
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
@@ -1176,7 +1206,7 @@ async fn test_next_index_zero() -> std::io::Result<()> {
     // Set next index to 0
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("next-index", "0")])
+        .set_form([("next-index", "0")])
         .to_request();
     let _ = test::call_service(&app, req).await;
     
@@ -1216,7 +1246,7 @@ async fn test_next_index_with_reorder() -> std::io::Result<()> {
     // Reorder and set next index in one request
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("image-name", "img4.png"), ("move-to", "0"), ("next-index", "0")])
+        .set_form([("image-name", "img4.png"), ("move-to", "0"), ("next-index", "0")])
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
@@ -1255,7 +1285,7 @@ async fn test_next_index_sequence() -> std::io::Result<()> {
     // Test jumping to index 0
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("next-index", "0")])
+        .set_form([("next-index", "0")])
         .to_request();
     let _ = test::call_service(&app, req).await;
 
@@ -1268,7 +1298,7 @@ async fn test_next_index_sequence() -> std::io::Result<()> {
     // Jump to index 2
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("next-index", "2")])
+        .set_form([("next-index", "2")])
         .to_request();
     let _ = test::call_service(&app, req).await;
 
@@ -1281,7 +1311,7 @@ async fn test_next_index_sequence() -> std::io::Result<()> {
     // Jump to index 4
     let req = test::TestRequest::post()
         .uri("/all-images")
-        .set_form(&[("next-index", "4")])
+        .set_form([("next-index", "4")])
         .to_request();
     let _ = test::call_service(&app, req).await;
 
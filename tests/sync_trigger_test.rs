@@ -0,0 +1,58 @@
+use actix_web::{test, web, App};
+use image_server_lib::sync_trigger::{setup_sync, SyncTrigger};
+use std::time::Duration;
+
+/// Tells whether a fetch cycle was asked for, without waiting for one
+async fn is_requested(trigger: &SyncTrigger) -> bool {
+    tokio::time::timeout(Duration::from_millis(50), trigger.requested()).await.is_ok()
+}
+
+#[actix_web::test]
+async fn test_overlapping_sync_requests_are_merged() {
+    let trigger = web::Data::new(SyncTrigger::new(None));
+    let app = test::init_service(
+        App::new()
+            .app_data(trigger.clone())
+            .configure(setup_sync)
+    ).await;
+
+    assert!(!is_requested(&trigger).await);
+
+    // Several requests while the fetcher is busy result in a single cycle
+    for _ in 0..3 {
+        let req = test::TestRequest::post().uri("/sync").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 202);
+    }
+    assert!(is_requested(&trigger).await);
+    assert!(!is_requested(&trigger).await);
+}
+
+#[actix_web::test]
+async fn test_sync_requests_need_the_token() {
+    let trigger = web::Data::new(SyncTrigger::new(Some("secret".to_string())));
+    let app = test::init_service(
+        App::new()
+            .app_data(trigger.clone())
+            .configure(setup_sync)
+    ).await;
+
+    for req in [
+        test::TestRequest::post().uri("/sync"),
+        test::TestRequest::post().uri("/sync?token=wrong"),
+        test::TestRequest::post().uri("/sync").insert_header(("Authorization", "Bearer wrong")),
+    ] {
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), 401);
+    }
+    assert!(!is_requested(&trigger).await);
+
+    for req in [
+        test::TestRequest::post().uri("/sync?token=secret"),
+        test::TestRequest::post().uri("/sync").insert_header(("Authorization", "Bearer secret")),
+    ] {
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), 202);
+        assert!(is_requested(&trigger).await);
+    }
+}
@@ -0,0 +1,261 @@
+use mockito::{Matcher, Server};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use image_server_lib::{FetcherConfig, WebDavConfig, WebDavSource, fetch_and_download_images};
+
+struct TestArgs {
+    webdav_url: String,
+    metadata_sidecars: bool,
+}
+
+impl WebDavConfig for TestArgs {
+    fn webdav_url(&self) -> &str {
+        &self.webdav_url
+    }
+
+    fn webdav_username(&self) -> Option<&str> {
+        Some("alice")
+    }
+
+    fn webdav_password(&self) -> Option<&str> {
+        Some("secret")
+    }
+}
+
+impl FetcherConfig for TestArgs {
+    fn metadata_sidecars(&self) -> bool {
+        self.metadata_sidecars
+    }
+}
+
+/// Lists the originals in a directory, leaving out the fetcher's hidden bookkeeping files
+fn list_originals(dir: &Path) -> Vec<PathBuf> {
+    let mut originals: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("Failed to read directory")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| !path.file_name().unwrap().to_string_lossy().starts_with('.'))
+        .collect();
+    originals.sort();
+    originals
+}
+
+/// File name of an original without the asset ID prefix
+fn original_name(path: &Path) -> String {
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    name.split("--_--").nth(1).unwrap().to_string()
+}
+
+/// A PROPFIND response entry for a file
+fn file_response(href: &str, content_type: &str, etag: &str) -> String {
+    format!(r#"<d:response>
+    <d:href>{}</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype/>
+        <d:getcontenttype>{}</d:getcontenttype>
+        <d:getetag>"{}"</d:getetag>
+        <d:getlastmodified>Sat, 15 Jun 2024 10:30:00 GMT</d:getlastmodified>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>"#, href, content_type, etag)
+}
+
+/// A PROPFIND response entry for a folder
+fn folder_response(href: &str) -> String {
+    format!(r#"<d:response>
+    <d:href>{}</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype><d:collection/></d:resourcetype>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+    <d:propstat>
+      <d:prop>
+        <d:getcontenttype/>
+        <d:getetag/>
+      </d:prop>
+      <d:status>HTTP/1.1 404 Not Found</d:status>
+    </d:propstat>
+  </d:response>"#, href)
+}
+
+fn multistatus(responses: &[String]) -> String {
+    format!(r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:">
+  {}
+</d:multistatus>"#, responses.join("\n  "))
+}
+
+/// Mocks the listing of a folder, which must be requested one level at a time
+fn mock_folder(server: &mut Server, path: &str, responses: &[String]) -> mockito::Mock {
+    server.mock("PROPFIND", path)
+        .match_header("depth", "1")
+        .match_header("authorization", Matcher::Regex("^Basic ".to_string()))
+        .with_status(207)
+        .with_header("content-type", "application/xml; charset=utf-8")
+        .with_body(multistatus(responses))
+        .create()
+}
+
+#[tokio::test]
+async fn test_webdav_source() -> anyhow::Result<()> {
+    let mut server = Server::new_async().await;
+
+    let root = mock_folder(&mut server, "/dav/photos/", &[
+        folder_response("/dav/photos/"),
+        file_response("/dav/photos/beach.jpg", "image/jpeg", "etag-beach"),
+        folder_response("/dav/photos/2024%20Summer/"),
+        file_response("/dav/photos/notes.txt", "text/plain", "etag-notes"),
+        file_response("/dav/photos/.hidden.jpg", "image/jpeg", "etag-hidden"),
+    ]).expect(2);
+    let subfolder = mock_folder(&mut server, "/dav/photos/2024%20Summer/", &[
+        folder_response("/dav/photos/2024%20Summer/"),
+        // Some servers report no useful content type, the extension decides then
+        file_response("/dav/photos/2024%20Summer/Mountain%20View.PNG", "application/octet-stream", "etag-mountain"),
+    ]).expect(2);
+
+    let beach = server.mock("GET", "/dav/photos/beach.jpg")
+        .match_header("authorization", Matcher::Regex("^Basic ".to_string()))
+        .with_status(200)
+        .with_body("beach image")
+        .expect(1)
+        .create();
+    let mountain = server.mock("GET", "/dav/photos/2024%20Summer/Mountain%20View.PNG")
+        .with_status(200)
+        .with_body("mountain image")
+        .expect(1)
+        .create();
+
+    let temp_dir = tempdir()?;
+    let originals = temp_dir.path().to_str().unwrap();
+    // Without the trailing slash, as users tend to copy the URL
+    let args = TestArgs { webdav_url: format!("{}/dav/photos", server.url()), metadata_sidecars: true };
+    let client = reqwest::Client::new();
+    let source = WebDavSource::new(&client, &args);
+
+    let summary = fetch_and_download_images(&source, &args, originals, 10).await?;
+    assert_eq!(summary.downloaded, 2);
+
+    let files = list_originals(temp_dir.path());
+    let mut names: Vec<String> = files.iter().map(|path| original_name(path)).collect();
+    names.sort();
+    assert_eq!(names, vec!["Mountain View.PNG", "Mountain View.json", "beach.jpg", "beach.json"]);
+
+    let beach_file = files.iter().find(|path| original_name(path) == "beach.jpg").unwrap();
+    assert_eq!(fs::read(beach_file)?, b"beach image");
+
+    let sidecar = files.iter().find(|path| original_name(path) == "beach.json").unwrap();
+    let sidecar: serde_json::Value = serde_json::from_str(&fs::read_to_string(sidecar)?)?;
+    assert_eq!(sidecar["originalFileName"], "beach.jpg");
+    assert_eq!(sidecar["capturedAt"], "2024-06-15T10:30:00Z");
+
+    // Unchanged ETags, nothing is downloaded again
+    let summary = fetch_and_download_images(&source, &args, originals, 10).await?;
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(summary.skipped, 2);
+
+    root.assert();
+    subfolder.assert();
+    beach.assert();
+    mountain.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_webdav_source_follows_etag_changes() -> anyhow::Result<()> {
+    let mut server = Server::new_async().await;
+    let temp_dir = tempdir()?;
+    let originals = temp_dir.path().to_str().unwrap();
+    let args = TestArgs { webdav_url: format!("{}/dav/photos/", server.url()), metadata_sidecars: false };
+    let client = reqwest::Client::new();
+    let source = WebDavSource::new(&client, &args);
+
+    let listing = mock_folder(&mut server, "/dav/photos/", &[
+        folder_response("/dav/photos/"),
+        file_response("/dav/photos/kept.jpg", "image/jpeg", "etag-kept"),
+        file_response("/dav/photos/deleted.jpg", "image/jpeg", "etag-deleted"),
+        file_response("/dav/photos/edited.jpg", "image/jpeg", "etag-edited-1"),
+    ]);
+    server.mock("GET", "/dav/photos/kept.jpg").with_body("kept image").create();
+    server.mock("GET", "/dav/photos/deleted.jpg").with_body("deleted image").create();
+    let edited_v1 = server.mock("GET", "/dav/photos/edited.jpg").with_body("edited image").create();
+
+    fetch_and_download_images(&source, &args, originals, 10).await?;
+    assert_eq!(list_originals(temp_dir.path()).len(), 3);
+
+    // A photo deleted from the folder goes away, an edited one gets a new ETag and is fetched again
+    listing.remove();
+    edited_v1.remove();
+    mock_folder(&mut server, "/dav/photos/", &[
+        folder_response("/dav/photos/"),
+        file_response("/dav/photos/kept.jpg", "image/jpeg", "etag-kept"),
+        file_response("/dav/photos/edited.jpg", "image/jpeg", "etag-edited-2"),
+    ]);
+    server.mock("GET", "/dav/photos/edited.jpg").with_body("edited image, now cropped").create();
+
+    let summary = fetch_and_download_images(&source, &args, originals, 10).await?;
+    assert_eq!(summary.removed, 2);
+    assert_eq!(summary.downloaded, 1);
+
+    let files = list_originals(temp_dir.path());
+    let mut names: Vec<String> = files.iter().map(|path| original_name(path)).collect();
+    names.sort();
+    assert_eq!(names, vec!["edited.jpg", "kept.jpg"]);
+
+    let edited = files.iter().find(|path| original_name(path) == "edited.jpg").unwrap();
+    assert_eq!(fs::read(edited)?, b"edited image, now cropped");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_webdav_listing_error() -> anyhow::Result<()> {
+    let mut server = Server::new_async().await;
+    server.mock("PROPFIND", "/dav/photos/")
+        .with_status(401)
+        .with_body("Unauthorized")
+        .create();
+
+    let temp_dir = tempdir()?;
+    fs::write(temp_dir.path().join("abc--_--existing.jpg"), b"existing image")?;
+    let args = TestArgs { webdav_url: format!("{}/dav/photos/", server.url()), metadata_sidecars: false };
+    let client = reqwest::Client::new();
+    let source = WebDavSource::new(&client, &args);
+
+    let result = fetch_and_download_images(&source, &args, temp_dir.path().to_str().unwrap(), 10).await;
+    assert!(result.is_err(), "A failed listing must not look like an empty folder");
+    assert_eq!(list_originals(temp_dir.path()).len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_webdav_folder_listing_itself_encoded_differently() -> anyhow::Result<()> {
+    let mut server = Server::new_async().await;
+
+    mock_folder(&mut server, "/dav/photos/", &[
+        folder_response("/dav/photos/"),
+        folder_response("/dav/photos/Trip%20(2024)/"),
+    ]);
+    // Like Nextcloud, the folder lists itself with percent-encoded parentheses
+    let trip = mock_folder(&mut server, "/dav/photos/Trip%20(2024)/", &[
+        folder_response("/dav/photos/Trip%20%282024%29/"),
+        file_response("/dav/photos/Trip%20%282024%29/lake.jpg", "image/jpeg", "etag-lake"),
+    ]).expect(1);
+    server.mock("GET", "/dav/photos/Trip%20%282024%29/lake.jpg").with_body("lake image").create();
+
+    let temp_dir = tempdir()?;
+    let args = TestArgs { webdav_url: format!("{}/dav/photos/", server.url()), metadata_sidecars: false };
+    let client = reqwest::Client::new();
+    let source = WebDavSource::new(&client, &args);
+
+    let summary = fetch_and_download_images(&source, &args, temp_dir.path().to_str().unwrap(), 10).await?;
+    assert_eq!(summary.downloaded, 1);
+    trip.assert();
+
+    Ok(())
+}